@@ -0,0 +1,73 @@
+// Plays engine configurations against each other and prints the outcome as JSON, e.g.
+//   cargo run --release --bin arena -- --games 100 --fuel-a 2000000 --fuel-b 500000
+use pylos::{
+    cli::Flags,
+    logic::{
+        ai::DEFAULT_FUEL,
+        arena::{run_arena, ArenaConfig, Engine},
+        evaluation::Evaluation,
+    },
+};
+use std::{path::Path, process::exit};
+
+const USAGE: &str = "usage: arena [--games N] [--fuel-a FUEL] [--fuel-b FUEL] \
+                     [--weights-a FILE] [--weights-b FILE] [--opening-moves N] [--seed SEED]";
+
+fn parse_args() -> Result<ArenaConfig, String> {
+    let mut games = 20;
+    let mut fuel = [DEFAULT_FUEL, DEFAULT_FUEL];
+    let mut weights: [Option<String>; 2] = [None, None];
+    let mut opening_moves = 4;
+    let mut seed = rand::random();
+
+    let mut flags = Flags::from_env();
+    while let Some(flag) = flags.next_flag()? {
+        match flag.name.as_str() {
+            "--games" => games = flag.parse()?,
+            "--fuel-a" => fuel[0] = flag.parse()?,
+            "--fuel-b" => fuel[1] = flag.parse()?,
+            "--weights-a" => weights[0] = Some(flag.value),
+            "--weights-b" => weights[1] = Some(flag.value),
+            "--opening-moves" => opening_moves = flag.parse()?,
+            "--seed" => seed = flag.parse()?,
+            _ => return Err(flag.unknown()),
+        }
+    }
+
+    let mut engines = Vec::with_capacity(2);
+    for (fuel, weights) in fuel.into_iter().zip(weights) {
+        let (name, evaluation) = match weights {
+            Some(path) => {
+                let evaluation = Evaluation::load(Path::new(&path))
+                    .map_err(|e| format!("could not read {}: {}", path, e))?;
+                (format!("minmax-{}-{}", fuel, path), evaluation)
+            }
+            None => (format!("minmax-{}", fuel), Evaluation::default()),
+        };
+        engines.push(Engine {
+            name,
+            fuel,
+            evaluation,
+        });
+    }
+
+    Ok(ArenaConfig {
+        engines: engines.try_into().unwrap(),
+        games,
+        opening_moves,
+        seed,
+    })
+}
+
+fn main() {
+    let config = parse_args().unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        exit(2);
+    });
+    eprintln!(
+        "[arena]: {} games of {} vs {}, seed {}",
+        config.games, config.engines[0].name, config.engines[1].name, config.seed
+    );
+    let report = run_arena(&config);
+    println!("{}", serde_json::to_string(&report).unwrap());
+}
@@ -0,0 +1,59 @@
+// Builds the computer's opening book by searching the first moves offline, e.g.
+//   cargo run --release --bin opening_book -- --depth 4 --output book.jsonl
+// The server uses the book when PYLOS_OPENING_BOOK points at the file.
+use pylos::{
+    cli::Flags,
+    logic::{
+        ai::DEFAULT_FUEL,
+        evaluation::BALL,
+        opening_book::{generate, BookSettings},
+    },
+};
+use std::{path::PathBuf, process::exit};
+
+const USAGE: &str = "usage: opening_book --output FILE [--depth N] [--fuel FUEL] \
+                     [--candidates N] [--margin SCORE]";
+
+fn parse_args() -> Result<(BookSettings, PathBuf), String> {
+    let mut settings = BookSettings {
+        depth: 4,
+        fuel: DEFAULT_FUEL,
+        candidates: 3,
+        margin: 20 * BALL,
+    };
+    let mut output = None;
+
+    let mut flags = Flags::from_env();
+    while let Some(flag) = flags.next_flag()? {
+        match flag.name.as_str() {
+            "--depth" => settings.depth = flag.parse()?,
+            "--fuel" => settings.fuel = flag.parse()?,
+            "--candidates" => settings.candidates = flag.parse()?,
+            "--margin" => settings.margin = flag.parse()?,
+            "--output" => output = Some(PathBuf::from(flag.value)),
+            _ => return Err(flag.unknown()),
+        }
+    }
+
+    let output = output.ok_or("--output is required")?;
+    Ok((settings, output))
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let (settings, output) = parse_args().unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        exit(2);
+    });
+
+    let book = generate(&settings);
+    if let Err(e) = book.save(&output) {
+        eprintln!("could not write {:?}: {}", output, e);
+        exit(1);
+    }
+    eprintln!(
+        "[opening_book]: wrote {} positions to {:?}",
+        book.len(),
+        output
+    );
+}
@@ -0,0 +1,85 @@
+// Plays the engine against itself and writes the games as training data for `tune`, e.g.
+//   cargo run --release --bin self_play -- --games 1000 --output games.jsonl
+use pylos::{
+    cli::Flags,
+    logic::{
+        evaluation::Evaluation,
+        training::{save_games, self_play_game, SelfPlayGame, SelfPlaySettings},
+    },
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{path::PathBuf, process::exit};
+
+const USAGE: &str = "usage: self_play --output FILE [--games N] [--fuel FUEL] \
+                     [--opening-moves N] [--exploration P] [--weights FILE] [--seed SEED]";
+
+struct Args {
+    games: usize,
+    settings: SelfPlaySettings,
+    seed: u64,
+    output: PathBuf,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut games = 100;
+    let mut settings = SelfPlaySettings {
+        fuel: 100_000,
+        evaluation: Evaluation::default(),
+        opening_moves: 4,
+        exploration: 0.05,
+    };
+    let mut seed = rand::random();
+    let mut output = None;
+
+    let mut flags = Flags::from_env();
+    while let Some(flag) = flags.next_flag()? {
+        match flag.name.as_str() {
+            "--games" => games = flag.parse()?,
+            "--fuel" => settings.fuel = flag.parse()?,
+            "--opening-moves" => settings.opening_moves = flag.parse()?,
+            "--exploration" => settings.exploration = flag.parse()?,
+            "--weights" => settings.evaluation = flag.evaluation()?,
+            "--seed" => seed = flag.parse()?,
+            "--output" => output = Some(PathBuf::from(flag.value)),
+            _ => return Err(flag.unknown()),
+        }
+    }
+    if !(0.0..=1.0).contains(&settings.exploration) {
+        return Err("--exploration must be between 0 and 1".to_owned());
+    }
+
+    let output = output.ok_or("--output is required")?;
+    Ok(Args {
+        games,
+        settings,
+        seed,
+        output,
+    })
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        exit(2);
+    });
+
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let games: Vec<SelfPlayGame> = (0..args.games)
+        .map(|i| {
+            let game = self_play_game(&args.settings, &mut rng);
+            eprintln!(
+                "[self_play]: game {}/{}: {} moves, winner {:?}",
+                i + 1,
+                args.games,
+                game.moves.len(),
+                game.winner
+            );
+            game
+        })
+        .collect();
+
+    if let Err(e) = save_games(&games, &args.output) {
+        eprintln!("could not write {:?}: {}", args.output, e);
+        exit(1);
+    }
+}
@@ -0,0 +1,93 @@
+// Tunes the evaluation weights on self-play games, e.g.
+//   cargo run --release --bin tune -- --input games.jsonl --output weights.json
+// The server uses the weights when PYLOS_EVALUATION points at the file.
+use pylos::{
+    cli::Flags,
+    logic::{
+        evaluation::{Evaluation, FEATURE_NAMES},
+        training::{error, fit_scale, load_games, samples, tune, Sample},
+    },
+};
+use std::{path::PathBuf, process::exit};
+
+const USAGE: &str = "usage: tune --input FILE --output FILE [--weights FILE] \
+                     [--iterations N] [--learning-rate RATE]";
+
+struct Args {
+    input: PathBuf,
+    output: PathBuf,
+    evaluation: Evaluation,
+    iterations: usize,
+    learning_rate: f64,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut input = None;
+    let mut output = None;
+    let mut evaluation = Evaluation::default();
+    let mut iterations = 1000;
+    let mut learning_rate = 1.0;
+
+    let mut flags = Flags::from_env();
+    while let Some(flag) = flags.next_flag()? {
+        match flag.name.as_str() {
+            "--input" => input = Some(PathBuf::from(flag.value)),
+            "--output" => output = Some(PathBuf::from(flag.value)),
+            "--weights" => evaluation = flag.evaluation()?,
+            "--iterations" => iterations = flag.parse()?,
+            "--learning-rate" => learning_rate = flag.parse()?,
+            _ => return Err(flag.unknown()),
+        }
+    }
+
+    Ok(Args {
+        input: input.ok_or("--input is required")?,
+        output: output.ok_or("--output is required")?,
+        evaluation,
+        iterations,
+        learning_rate,
+    })
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        exit(2);
+    });
+
+    let games = load_games(&args.input).unwrap_or_else(|e| {
+        eprintln!("could not read {:?}: {}", args.input, e);
+        exit(1);
+    });
+    let samples: Vec<Sample> = games.iter().flat_map(samples).collect();
+    if samples.is_empty() {
+        eprintln!("no positions in {:?}", args.input);
+        exit(1);
+    }
+
+    let scale = fit_scale(&args.evaluation, &samples);
+    let before = error(&args.evaluation, scale, &samples);
+    let tuned = tune(
+        &args.evaluation,
+        scale,
+        &samples,
+        args.iterations,
+        args.learning_rate,
+    );
+    let after = error(&tuned, scale, &samples);
+
+    eprintln!(
+        "[tune]: {} positions from {} games, scale {}",
+        samples.len(),
+        games.len(),
+        scale
+    );
+    eprintln!("[tune]: error {:.6} -> {:.6}", before, after);
+    for (name, weight) in FEATURE_NAMES.iter().zip(tuned.weights) {
+        eprintln!("[tune]: {} = {:.3}", name, weight);
+    }
+    if let Err(e) = tuned.save(&args.output) {
+        eprintln!("could not write {:?}: {}", args.output, e);
+        exit(1);
+    }
+}
@@ -0,0 +1,80 @@
+// The flags of the offline tools in src/bin, each of them followed by its value, e.g.
+//   --games 100 --weights weights.json
+use crate::logic::evaluation::Evaluation;
+use std::{env, iter::Skip, str::FromStr};
+
+pub struct Flag {
+    pub name: String,
+    pub value: String,
+}
+
+impl Flag {
+    pub fn parse<T: FromStr>(&self) -> Result<T, String> {
+        self.value
+            .parse()
+            .map_err(|_| format!("invalid value for {}: {}", self.name, self.value))
+    }
+
+    // The value names a file of evaluation weights, as written by `tune`
+    pub fn evaluation(&self) -> Result<Evaluation, String> {
+        Evaluation::load(self.value.as_ref())
+            .map_err(|e| format!("could not read {}: {}", self.value, e))
+    }
+
+    pub fn unknown(&self) -> String {
+        format!("unknown flag {}", self.name)
+    }
+}
+
+pub struct Flags<I> {
+    args: I,
+}
+
+impl Flags<Skip<env::Args>> {
+    // The flags the program was started with
+    pub fn from_env() -> Self {
+        Flags::new(env::args().skip(1))
+    }
+}
+
+impl<I: Iterator<Item = String>> Flags<I> {
+    pub fn new(args: I) -> Self {
+        Flags { args }
+    }
+
+    // None once all the flags are read
+    pub fn next_flag(&mut self) -> Result<Option<Flag>, String> {
+        let Some(name) = self.args.next() else {
+            return Ok(None);
+        };
+        let value = self
+            .args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", name))?;
+        Ok(Some(Flag { name, value }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_take_one_value_each() {
+        let args = ["--games", "12", "--exploration", "lots", "--seed"];
+        let mut flags = Flags::new(args.into_iter().map(String::from));
+
+        let games = flags.next_flag().unwrap().unwrap();
+        assert_eq!(games.parse::<usize>(), Ok(12));
+        let exploration = flags.next_flag().unwrap().unwrap();
+        assert_eq!(
+            exploration.parse::<f64>(),
+            Err("invalid value for --exploration: lots".to_owned())
+        );
+        assert_eq!(
+            flags.next_flag().err(),
+            Some("--seed needs a value".to_owned())
+        );
+        assert!(flags.next_flag().unwrap().is_none());
+    }
+}
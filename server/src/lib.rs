@@ -1,3 +1,4 @@
+pub mod cli;
 pub mod logic;
 pub mod protocol;
 pub mod state;
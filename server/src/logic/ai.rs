@@ -1,14 +1,28 @@
 use crate::logic::amove::Move;
 
-use super::{board::Board, player_side::PlayerSide};
+use super::{
+    board::Board,
+    evaluation::{Evaluation, WIN},
+    opening_book::OpeningBook,
+    player_side::PlayerSide,
+};
 
 use log::info;
 use rand::Rng;
-use std::{collections::HashMap, time::Instant};
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+// The number of positions the search may visit, roughly
+pub const DEFAULT_FUEL: i32 = 2_000_000;
+
+// The score and best move of every position the search went through
+type SearchTable = HashMap<Board, (i32, Option<Move>)>;
 
 pub struct AI {
     pub side: PlayerSide,
     pub board: Board,
+    pub fuel: i32,
+    pub evaluation: Evaluation,
+    pub opening_book: Option<Arc<OpeningBook>>,
 }
 
 fn average<I>(iter: I) -> Option<f64>
@@ -39,11 +53,29 @@ fn bool_to_int(b: bool) -> i32 {
     }
 }
 
+// The line of best moves the search recorded from [board] on
+fn follow_table(mut board: Board, table: &SearchTable, length: usize) -> Vec<Move> {
+    let mut line = vec![];
+    while line.len() < length {
+        let Some(&(_, Some(mv))) = table.get(&board) else {
+            break;
+        };
+        if board.make_move(mv).is_err() {
+            break;
+        }
+        line.push(mv);
+    }
+    line
+}
+
 impl AI {
     pub fn new(side: PlayerSide) -> AI {
         AI {
             side,
             board: Board::new(),
+            fuel: DEFAULT_FUEL,
+            evaluation: Evaluation::default(),
+            opening_book: None,
         }
     }
 
@@ -64,41 +96,133 @@ impl AI {
     }
 
     pub fn make_minmax_move(&mut self) -> Option<Board> {
-        fn terminate_search(board: &Board) -> (i32, Option<Move>) {
-            fn value(board: &Board) -> i32 {
-                board.number_of_balls_in_reserve(board.get_turn()) as i32
-                    - board.number_of_balls_in_reserve(!board.get_turn()) as i32
+        let mv = self.get_minmax_move()?;
+        let _ = self.board.make_move(mv);
+        Some(self.board.clone())
+    }
+
+    pub fn get_minmax_move(&self) -> Option<Move> {
+        let start = Instant::now();
+        let (score, omove) = self.search();
+        let duration = start.elapsed();
+
+        if let Some(mv) = omove {
+            info!(
+                "[minmax_moves, turn={:?}, mv={}, score={}, duration={:?}]",
+                self.board.get_turn(),
+                mv,
+                score,
+                duration
+            );
+        }
+        omove
+    }
+
+    // A move from the opening book if it knows the position, or else the search's move
+    pub fn get_move(&self) -> Option<Move> {
+        let book_move = self
+            .opening_book
+            .as_ref()
+            .and_then(|book| book.choose(&self.board, &mut rand::thread_rng()));
+        if let Some(mv) = book_move {
+            info!("[book_move, turn={:?}, mv={}]", self.board.get_turn(), mv);
+            return Some(mv);
+        }
+        self.get_minmax_move()
+    }
+
+    // The best move and its score, from the point of view of the side to move
+    pub fn search(&self) -> (i32, Option<Move>) {
+        self.search_table().0
+    }
+
+    // Every legal move with its search score, from the point of view of the side to move
+    pub fn score_moves(&self) -> Vec<(Move, i32)> {
+        self.score_moves_with_variation(0).0
+    }
+
+    // [score_moves], along with the principal variation of up to [length] moves that starts with
+    // the best of them. The variation is read from the table of the search that scored the best
+    // move, so nothing is searched twice.
+    pub fn score_moves_with_variation(&self, length: usize) -> (Vec<(Move, i32)>, Vec<Move>) {
+        let mut scored = vec![];
+        let mut best: Option<(i32, Move, Board, SearchTable)> = None;
+        for mv in self.board.get_valid_moves() {
+            let mut next = self.board.clone();
+            let _ = next.make_move(mv);
+            let (score, table) = if next.is_game_over() {
+                (WIN, SearchTable::new())
+            } else {
+                let ai = AI {
+                    side: next.get_turn(),
+                    board: next.clone(),
+                    fuel: self.fuel,
+                    evaluation: self.evaluation,
+                    opening_book: None,
+                };
+                let ((score, _), table) = ai.search_table();
+                // After a take-back the same side moves again
+                if next.get_turn() == self.board.get_turn() {
+                    (score, table)
+                } else {
+                    (-score, table)
+                }
+            };
+            // The first of equally good moves is the best, as a stable sort would have it
+            if best
+                .as_ref()
+                .is_none_or(|(best_score, ..)| score > *best_score)
+            {
+                best = Some((score, mv, next, table));
             }
+            scored.push((mv, score));
+        }
 
+        let variation = match best {
+            Some((_, best_move, next, table)) if length > 0 => {
+                let mut variation = vec![best_move];
+                variation.extend(follow_table(next, &table, length - 1));
+                variation
+            }
+            _ => vec![],
+        };
+        (scored, variation)
+    }
+
+    // The search's result along with the positions it went through
+    fn search_table(&self) -> ((i32, Option<Move>), SearchTable) {
+        fn terminate_search(board: &Board, evaluation: &Evaluation) -> (i32, Option<Move>) {
+            let value = evaluation.value(board);
             let moves = board.get_valid_moves();
             if moves.is_empty() {
-                (value(board), None)
+                (value, None)
             } else {
-                (value(board), Some(moves[0]))
+                (value, Some(moves[0]))
             }
         }
 
         fn minmax(
             board: Board,
             fuel: i32,
-            hash: &mut HashMap<Board, (i32, Option<Move>)>,
+            evaluation: &Evaluation,
+            hash: &mut SearchTable,
         ) -> (i32, Option<Move>) {
             if let Some(res) = hash.get(&board) {
                 return *res;
             }
 
             if board.get_move_number() > 200 {
-                return terminate_search(&board);
+                return terminate_search(&board, evaluation);
             }
             if fuel <= 0 {
-                return terminate_search(&board);
+                return terminate_search(&board, evaluation);
             }
 
             if board.is_game_over() {
                 if board.get_winner().unwrap() == board.get_turn() {
-                    return (1000, None);
+                    return (WIN, None);
                 } else {
-                    return (-1000, None);
+                    return (-WIN, None);
                 }
             }
 
@@ -118,7 +242,10 @@ impl AI {
 
                     (new_board, mv, mult)
                 })
-                .map(|(board, mv, mult)| (minmax(board, fuel / n - 1, hash).0, mv, mult))
+                .map(|(board, mv, mult)| {
+                    let (score, _) = minmax(board, fuel / n - 1, evaluation, hash);
+                    (score, mv, mult)
+                })
                 .map(|(score, mv, mult)| (mult * score, Some(mv)))
                 .collect::<Vec<_>>();
 
@@ -138,25 +265,8 @@ impl AI {
             (average_score, best_move)
         }
 
-        let fuel = 2_000_000;
-        let mut hash: HashMap<Board, (i32, Option<Move>)> = HashMap::new();
-
-        let start = Instant::now();
-        let (score, omove) = minmax(self.board.clone(), fuel, &mut hash);
-        let duration = start.elapsed();
-
-        if let Some(mv) = omove {
-            info!(
-                "[minmax_moves, turn={:?}, mv={}, score={}, duration={:?}]",
-                self.board.get_turn(),
-                mv,
-                score,
-                duration
-            );
-            let _ = self.board.make_move(mv);
-            Some(self.board.clone())
-        } else {
-            None
-        }
+        let mut hash = SearchTable::new();
+        let result = minmax(self.board.clone(), self.fuel, &self.evaluation, &mut hash);
+        (result, hash)
     }
 }
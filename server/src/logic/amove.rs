@@ -3,7 +3,9 @@ use std::fmt;
 use super::{ball::Ball, player_side::PlayerSide};
 use crate::logic::{board_side::BoardSide, index::Index};
 
-#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq,
+)]
 pub struct Move {
     pub from: Ball,
     pub to: Ball,
@@ -86,3 +88,15 @@ impl Move {
         )
     }
 }
+
+impl Move {
+    // Moves that differ only in the reserve slot a ball leaves or returns to are the same move
+    pub fn is_equivalent(&self, other: &Move) -> bool {
+        let same_place = |a: Ball, b: Ball| {
+            a.player == b.player
+                && (a.index == b.index
+                    || (a.index.b != BoardSide::Center && b.index.b != BoardSide::Center))
+        };
+        same_place(self.from, other.from) && same_place(self.to, other.to)
+    }
+}
@@ -0,0 +1,314 @@
+use super::{
+    ai::AI,
+    amove::Move,
+    board::Board,
+    evaluation::{Evaluation, BALL},
+    player_side::PlayerSide,
+};
+use std::time::{Duration, Instant};
+
+// Each depth searches with four times the fuel of the one before; the last one about matches the
+// computer's own search
+pub const MAX_DEPTH: u32 = 6;
+pub const MAX_TIME: Duration = Duration::from_secs(10);
+const BASE_FUEL: i32 = 1_000;
+const PRINCIPAL_VARIATION_LENGTH: usize = 10;
+
+// How long an analysis may run: a fixed depth, or as deep as it gets within the time
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq,
+)]
+pub enum AnalysisLimit {
+    Depth(u32),
+    Time(u64), // In milliseconds
+}
+
+impl Default for AnalysisLimit {
+    fn default() -> Self {
+        AnalysisLimit::Depth(5)
+    }
+}
+
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq,
+)]
+pub struct MoveScore {
+    pub mv: Move,
+    pub score: i32, // In hundredths of a ball, for the side to move; a won game is worth [WIN]
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+pub struct PositionAnalysis {
+    pub depth: u32, // The depth the scores come from
+    pub score: i32,
+    pub best_move: Move,
+    pub principal_variation: Vec<Move>, // Starting with [best_move]
+    pub top_moves: Vec<MoveScore>,      // Best first
+}
+
+pub fn fuel_for_depth(depth: u32) -> i32 {
+    BASE_FUEL << (2 * (depth.clamp(1, MAX_DEPTH) - 1))
+}
+
+fn analyze_at(
+    board: &Board,
+    evaluation: &Evaluation,
+    depth: u32,
+    lines: usize,
+) -> Result<PositionAnalysis, &'static str> {
+    if board.is_game_over() {
+        return Err("The game is over in this position");
+    }
+    let ai = AI {
+        fuel: fuel_for_depth(depth),
+        board: board.clone(),
+        evaluation: *evaluation,
+        ..AI::new(board.get_turn())
+    };
+    let (mut scored, principal_variation) =
+        ai.score_moves_with_variation(PRINCIPAL_VARIATION_LENGTH);
+    scored.sort_by_key(|&(_, score)| -score);
+    let &(best_move, score) = scored
+        .first()
+        .ok_or("There are no moves in this position")?;
+
+    Ok(PositionAnalysis {
+        depth,
+        score,
+        best_move,
+        principal_variation,
+        top_moves: scored
+            .into_iter()
+            .take(lines.max(1))
+            .map(|(mv, score)| MoveScore { mv, score })
+            .collect(),
+    })
+}
+
+// With a time limit the depth grows until the next one would likely not finish in time. The
+// first depth is always searched, so there is a result even for very short limits.
+pub fn analyze(
+    board: &Board,
+    evaluation: &Evaluation,
+    limit: AnalysisLimit,
+    lines: usize,
+) -> Result<PositionAnalysis, &'static str> {
+    match limit {
+        AnalysisLimit::Depth(depth) => {
+            analyze_at(board, evaluation, depth.clamp(1, MAX_DEPTH), lines)
+        }
+        AnalysisLimit::Time(millis) => {
+            let limit = Duration::from_millis(millis).min(MAX_TIME);
+            let start = Instant::now();
+            let mut analysis = analyze_at(board, evaluation, 1, lines)?;
+            let mut last = start.elapsed();
+            while analysis.depth < MAX_DEPTH && start.elapsed() + last * 4 <= limit {
+                let iteration = Instant::now();
+                analysis = analyze_at(board, evaluation, analysis.depth + 1, lines)?;
+                last = iteration.elapsed();
+            }
+            Ok(analysis)
+        }
+    }
+}
+
+// How much worse a move is than the best one, in balls in reserve
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq,
+)]
+pub enum MoveQuality {
+    Inaccuracy = 0, // 1 ball
+    Mistake = 1,    // 2 or 3 balls
+    Blunder = 2,    // 4 balls or more, or a won game thrown away
+}
+
+impl MoveQuality {
+    // Only whole balls count; a fraction of a ball is not worth mentioning
+    fn from_loss(loss: i32) -> Option<Self> {
+        match loss / BALL {
+            i32::MIN..=0 => None,
+            1 => Some(MoveQuality::Inaccuracy),
+            2..=3 => Some(MoveQuality::Mistake),
+            _ => Some(MoveQuality::Blunder),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+pub struct MoveAnnotation {
+    pub move_number: usize, // The move's index in the game
+    pub mv: Move,
+    pub side: PlayerSide,
+    pub score: i32, // From the point of view of [side]
+    pub best_move: Move,
+    pub best_score: i32,
+    pub quality: Option<MoveQuality>, // None if the move loses nothing worth mentioning
+    pub missed_square: Option<Move>,  // A move that would have completed a square instead
+    pub missed_take_backs: usize,     // How many balls that square would have won back
+}
+
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, Default, PartialEq,
+)]
+pub struct SideSummary {
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+    pub missed_squares: usize,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+pub struct GameAnnotation {
+    pub depth: u32,
+    pub moves: Vec<MoveAnnotation>,
+    pub white: SideSummary,
+    pub black: SideSummary,
+}
+
+// The balls a move wins back, if it completes a square: the take-backs that follow are made
+// with the first ball available, as only their number matters here
+fn take_backs_after(board: &Board, mv: Move) -> usize {
+    let mut next = board.clone();
+    if next.make_move(mv).is_err() || !next.take_back_rule() {
+        return 0;
+    }
+    let mut take_backs = 0;
+    while next.take_back_rule() {
+        let Some(&take_back) = next.get_valid_moves().first() else {
+            break;
+        };
+        if next.make_move(take_back).is_err() {
+            break;
+        }
+        take_backs += 1;
+    }
+    take_backs
+}
+
+// Scores every move of the game against the best one in its position
+pub fn annotate_game(
+    moves: &[Move],
+    evaluation: &Evaluation,
+    depth: u32,
+) -> Result<GameAnnotation, &'static str> {
+    let mut board = Board::new();
+    let mut annotation = GameAnnotation {
+        depth,
+        moves: vec![],
+        white: SideSummary::default(),
+        black: SideSummary::default(),
+    };
+    for (move_number, &mv) in moves.iter().enumerate() {
+        let ai = AI {
+            fuel: fuel_for_depth(depth),
+            board: board.clone(),
+            evaluation: *evaluation,
+            ..AI::new(board.get_turn())
+        };
+        let scored = ai.score_moves();
+        let score = scored
+            .iter()
+            .find(|(scored_move, _)| scored_move.is_equivalent(&mv))
+            .map(|&(_, score)| score)
+            .ok_or("The game contains an illegal move")?;
+        let &(best_move, best_score) = scored.iter().max_by_key(|&&(_, score)| score).unwrap();
+
+        // A take-back is not a square of its own, and a move that completes one misses nothing
+        let missed_square = if board.take_back_rule() || take_backs_after(&board, mv) > 0 {
+            None
+        } else {
+            scored
+                .iter()
+                .map(|&(candidate, _)| candidate)
+                .find(|&candidate| take_backs_after(&board, candidate) > 0)
+        };
+        let side = board.get_turn();
+        let quality = MoveQuality::from_loss(best_score - score);
+
+        let summary = match side {
+            PlayerSide::White => &mut annotation.white,
+            PlayerSide::Black => &mut annotation.black,
+        };
+        match quality {
+            Some(MoveQuality::Inaccuracy) => summary.inaccuracies += 1,
+            Some(MoveQuality::Mistake) => summary.mistakes += 1,
+            Some(MoveQuality::Blunder) => summary.blunders += 1,
+            None => {}
+        }
+        summary.missed_squares += missed_square.is_some() as usize;
+
+        annotation.moves.push(MoveAnnotation {
+            move_number,
+            mv,
+            side,
+            score,
+            best_move,
+            best_score,
+            quality,
+            missed_square,
+            missed_take_backs: missed_square.map_or(0, |square| take_backs_after(&board, square)),
+        });
+        board.make_move(mv)?;
+    }
+    Ok(annotation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analysis_of_the_opening() {
+        let board = Board::new();
+        let analysis = analyze(&board, &Evaluation::default(), AnalysisLimit::Depth(2), 3).unwrap();
+        assert_eq!(analysis.depth, 2);
+        assert_eq!(analysis.top_moves.len(), 3);
+        assert_eq!(analysis.top_moves[0].mv, analysis.best_move);
+        assert_eq!(analysis.principal_variation[0], analysis.best_move);
+        assert!(analysis
+            .top_moves
+            .windows(2)
+            .all(|w| w[0].score >= w[1].score));
+
+        // The principal variation is a legal line
+        let mut next = board.clone();
+        for &mv in &analysis.principal_variation {
+            next.make_move(mv).unwrap();
+        }
+
+        let timed = analyze(&board, &Evaluation::default(), AnalysisLimit::Time(0), 1).unwrap();
+        assert_eq!((timed.depth, timed.top_moves.len()), (1, 1));
+    }
+
+    #[test]
+    fn missed_square_is_annotated() {
+        // White builds three corners of a square, then plays elsewhere instead of completing it
+        let moves = [
+            Move::new_wrc((0, 0), (0, 0, 0)),
+            Move::new_brc((0, 0), (3, 3, 0)),
+            Move::new_wrc((0, 1), (1, 0, 0)),
+            Move::new_brc((0, 1), (3, 2, 0)),
+            Move::new_wrc((0, 2), (0, 1, 0)),
+            Move::new_brc((0, 2), (2, 3, 0)),
+            Move::new_wrc((1, 0), (3, 0, 0)),
+        ];
+        let annotation = annotate_game(&moves, &Evaluation::default(), 1).unwrap();
+        assert_eq!(annotation.moves.len(), moves.len());
+
+        let last = &annotation.moves[6];
+        assert_eq!(last.side, PlayerSide::White);
+        assert!(last
+            .missed_square
+            .unwrap()
+            .is_equivalent(&Move::new_wrc((1, 0), (1, 1, 0))));
+        assert_eq!(last.missed_take_backs, 2);
+        assert!(last.score < last.best_score);
+        assert!(last.quality.is_some());
+        assert_eq!(annotation.white.missed_squares, 1);
+        assert!(annotation.moves[..6]
+            .iter()
+            .all(|annotation| annotation.missed_square.is_none()));
+
+        assert!(annotate_game(&moves[1..], &Evaluation::default(), 1).is_err());
+    }
+}
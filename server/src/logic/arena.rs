@@ -0,0 +1,211 @@
+use super::{ai::AI, board::Board, evaluation::Evaluation, player_side::PlayerSide};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+// Games still going after this many moves are scored as draws
+pub const MAX_MOVES: usize = 200;
+
+// One side of an arena match
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct Engine {
+    pub name: String,
+    pub fuel: i32,
+    #[serde(default)]
+    pub evaluation: Evaluation,
+}
+
+impl Engine {
+    fn choose_move(&self, board: &Board) -> Option<super::amove::Move> {
+        let ai = AI {
+            side: board.get_turn(),
+            board: board.clone(),
+            fuel: self.fuel,
+            evaluation: self.evaluation,
+            opening_book: None,
+        };
+        ai.get_minmax_move()
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct ArenaConfig {
+    pub engines: [Engine; 2],
+    pub games: usize, // Played in pairs, so that both engines get each opening as white
+    pub opening_moves: usize, // Random moves played before the engines take over
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct GameOutcome {
+    winner: Option<PlayerSide>, // None if the game hit [MAX_MOVES]
+    moves: usize,
+    thinking: [(Duration, usize); 2], // Time spent and moves made by the white and black engine
+}
+
+fn random_opening(rng: &mut StdRng, moves: usize) -> Board {
+    let mut board = Board::new();
+    for _ in 0..moves {
+        let valid_moves = board.get_valid_moves();
+        if valid_moves.is_empty() || board.is_game_over() {
+            break;
+        }
+        let mv = valid_moves[rng.gen_range(0..valid_moves.len())];
+        let _ = board.make_move(mv);
+    }
+    board
+}
+
+fn play_game(mut board: Board, white: &Engine, black: &Engine) -> GameOutcome {
+    let mut thinking = [(Duration::ZERO, 0); 2];
+    let mut moves = 0;
+    while !board.is_game_over() && moves < MAX_MOVES {
+        let (engine, side) = match board.get_turn() {
+            PlayerSide::White => (white, 0),
+            PlayerSide::Black => (black, 1),
+        };
+        let start = Instant::now();
+        let Some(mv) = engine.choose_move(&board) else {
+            break;
+        };
+        thinking[side].0 += start.elapsed();
+        thinking[side].1 += 1;
+        if board.make_move(mv).is_err() {
+            break;
+        }
+        moves += 1;
+    }
+    GameOutcome {
+        winner: board.get_winner(),
+        moves,
+        thinking,
+    }
+}
+
+// The outcome of a match, from the first engine's point of view
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct ArenaReport {
+    pub engines: [Engine; 2],
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub score: f64,          // Between 0 and 1
+    pub elo_difference: f64, // Positive if the first engine is stronger
+    pub elo_error: f64,      // Half the width of the 95% confidence interval
+    pub average_move_ms: [f64; 2],
+    pub average_game_length: f64, // In moves
+}
+
+fn elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+// The Elo difference implied by the score, and its 95% error margin. Scores of 0 and 1 are
+// clamped to half a game away, which keeps the numbers finite.
+pub fn elo_difference(wins: usize, draws: usize, losses: usize) -> (f64, f64) {
+    let games = (wins + draws + losses) as f64;
+    if games == 0.0 {
+        return (0.0, 0.0);
+    }
+    let margin = 0.5 / games;
+    let score = ((wins as f64 + draws as f64 / 2.0) / games).clamp(margin, 1.0 - margin);
+
+    let deviation = ((wins as f64 * (1.0 - score).powi(2)
+        + draws as f64 * (0.5 - score).powi(2)
+        + losses as f64 * score.powi(2))
+        / games)
+        .sqrt();
+    let error = 1.96 * deviation / games.sqrt();
+    let low = (score - error).clamp(margin, 1.0 - margin);
+    let high = (score + error).clamp(margin, 1.0 - margin);
+    (elo(score), (elo(high) - elo(low)) / 2.0)
+}
+
+pub fn run_arena(config: &ArenaConfig) -> ArenaReport {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let [a, b] = &config.engines;
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+    let mut thinking = [(Duration::ZERO, 0); 2];
+    let mut total_moves = 0;
+
+    let mut opening = Board::new();
+    for game in 0..config.games {
+        // Each opening is played twice, with the colours swapped
+        let a_is_white = game % 2 == 0;
+        if a_is_white {
+            opening = random_opening(&mut rng, config.opening_moves);
+        }
+        let outcome = if a_is_white {
+            play_game(opening.clone(), a, b)
+        } else {
+            play_game(opening.clone(), b, a)
+        };
+
+        let a_side = if a_is_white {
+            PlayerSide::White
+        } else {
+            PlayerSide::Black
+        };
+        match outcome.winner {
+            Some(winner) if winner == a_side => wins += 1,
+            Some(_) => losses += 1,
+            None => draws += 1,
+        }
+        let (a_index, b_index) = if a_is_white { (0, 1) } else { (1, 0) };
+        for (engine, side) in [(0, a_index), (1, b_index)] {
+            thinking[engine].0 += outcome.thinking[side].0;
+            thinking[engine].1 += outcome.thinking[side].1;
+        }
+        total_moves += outcome.moves;
+    }
+
+    let games = config.games.max(1) as f64;
+    let (elo_difference, elo_error) = elo_difference(wins, draws, losses);
+    let average_move_ms =
+        thinking.map(|(time, moves)| time.as_secs_f64() * 1000.0 / moves.max(1) as f64);
+    ArenaReport {
+        engines: config.engines.clone(),
+        games: config.games,
+        wins,
+        draws,
+        losses,
+        score: (wins as f64 + draws as f64 / 2.0) / games,
+        elo_difference,
+        elo_error,
+        average_move_ms,
+        average_game_length: total_moves as f64 / games,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_from_score() {
+        assert_eq!(elo_difference(5, 0, 5), (0.0, elo_difference(5, 0, 5).1));
+        let (difference, error) = elo_difference(75, 0, 25);
+        assert!((difference - 190.8).abs() < 0.1, "{}", difference);
+        assert!(error > 0.0 && error < difference);
+        assert!(elo_difference(10, 0, 0).0.is_finite());
+    }
+
+    #[test]
+    fn deeper_search_wins() {
+        let engine = |name: &str, fuel| Engine {
+            name: name.to_owned(),
+            fuel,
+            evaluation: Evaluation::default(),
+        };
+        let config = ArenaConfig {
+            engines: [engine("deep", 2_000), engine("shallow", 0)],
+            games: 2,
+            opening_moves: 2,
+            seed: 7,
+        };
+        let report = run_arena(&config);
+        assert_eq!(report.wins + report.draws + report.losses, 2);
+        assert!(report.score > 0.5, "{:?}", report);
+        assert_eq!(run_arena(&config).wins, report.wins); // The seed fixes the openings
+    }
+}
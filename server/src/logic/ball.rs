@@ -1,6 +1,8 @@
 use super::{board_side::BoardSide, index::Index, player_side::PlayerSide};
 
-#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq,
+)]
 pub struct Ball {
     pub player: PlayerSide,
     pub index: Index,
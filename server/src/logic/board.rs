@@ -9,7 +9,7 @@ fn cross(xs: Range<i8>, ys: Range<i8>) -> impl Iterator<Item = (i8, i8)> {
 }
 
 #[allow(non_snake_case)]
-#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone)]
 pub struct BoardFrontend {
     pub nmove: u8,
     pub turn: PlayerSide,
@@ -66,6 +66,15 @@ impl Board {
         }
     }
 
+    // The position the moves lead to from the start of the game
+    pub fn replay(moves: &[Move]) -> Result<Board, &'static str> {
+        let mut board = Board::new();
+        for &mv in moves {
+            board.make_move(mv)?;
+        }
+        Ok(board)
+    }
+
     #[rustfmt::skip]
     fn get(&self, index: Index) -> Option<PlayerSide> {
         match index {
@@ -89,7 +98,7 @@ impl Board {
         }
     }
 
-    fn take_back_rule(&self) -> bool {
+    pub fn take_back_rule(&self) -> bool {
         self.take_back > 0
     }
 
@@ -250,8 +259,13 @@ impl Board {
         .collect()
     }
 
+    // Indices come from clients, so they are checked before anything is looked up at them
+    fn is_on_board(index: Index) -> bool {
+        Board::all_indices(index.b).contains(&index)
+    }
+
     fn is_child_index(index: Index, child: Index) -> bool {
-        Board::child_indices(index).iter().any(|&i| child == i)
+        Board::child_indices(index).contains(&child)
     }
 
     fn all_indices(board_side: BoardSide) -> Vec<Index> {
@@ -372,6 +386,36 @@ impl Board {
             })
     }
 
+    // The squares in which [player] has three balls and can place the fourth right away
+    pub fn square_threats(&self, player: PlayerSide) -> usize {
+        Board::all_indices(BoardSide::Center)
+            .into_iter()
+            .filter(|index| index.z > 0)
+            .map(Board::parent_indices)
+            .filter(|square| {
+                let own = square
+                    .iter()
+                    .filter(|&&i| self.get(i) == Some(player))
+                    .count();
+                let open: Vec<Index> = square
+                    .iter()
+                    .copied()
+                    .filter(|&i| self.is_index_empty(i))
+                    .collect();
+                own == 3 && open.len() == 1 && self.all_parent_exist(open[0])
+            })
+            .count()
+    }
+
+    // The balls of [player] on the board with nothing on top, which can be moved up or taken back
+    pub fn free_balls(&self, player: PlayerSide) -> usize {
+        Board::all_indices(BoardSide::Center)
+            .into_iter()
+            .filter(|&index| self.get(index) == Some(player))
+            .filter(|&index| !self.child_exists(index))
+            .count()
+    }
+
     fn move_is_possible(&self, player: PlayerSide) -> bool {
         if self.take_back_rule() {
             return self.take_back_is_possible(player);
@@ -416,6 +460,10 @@ impl Board {
     }
 
     fn validate_move(&mut self, mv: Move) -> Result<(), &'static str> {
+        if !Board::is_on_board(mv.from.index) || !Board::is_on_board(mv.to.index) {
+            return Err("The index is not on the board");
+        }
+
         if !self.player_color_matches_ball_color(mv) {
             return Err("Player is trying to move a ball of the opposite color");
         }
@@ -477,7 +525,17 @@ impl Board {
 
             Ok(())
         } else {
-            panic!("This branch is supposed to be unreachable!")
+            Err("Only take-back moves are allowed")
+        }
+    }
+
+    // What the side to move may play; nothing once the game is over. Moves out of the reserve
+    // take its first ball, though any other ball of the reserve is accepted as well.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if self.is_game_over() {
+            vec![]
+        } else {
+            self.get_valid_moves()
         }
     }
 
@@ -629,6 +687,19 @@ mod tests {
         assert!(board.make_move(Move::new_wrc((0, 0), (0, 0, 3))).is_err());
     }
 
+    #[test]
+    fn off_board_indices_are_refused() {
+        let mut board = Board::new();
+        assert!(board.make_move(Move::new_wrc((0, 0), (4, 0, 0))).is_err());
+        assert!(board.make_move(Move::new_wrc((0, 0), (0, -1, 0))).is_err());
+        assert!(board.make_move(Move::new_wrc((0, 0), (3, 3, 1))).is_err());
+        assert!(board.make_move(Move::new_wrc((5, 0), (0, 0, 0))).is_err());
+        assert!(board
+            .make_move(Move::new_wrc((i8::MAX, i8::MIN), (0, 0, 0)))
+            .is_err());
+        assert!(board.make_move(Move::new_wrc((0, 0), (0, 0, 0))).is_ok());
+    }
+
     #[test]
     fn no_wrong_color_move() {
         let mut board = Board::new();
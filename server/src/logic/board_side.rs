@@ -25,3 +25,9 @@ impl fmt::Display for BoardSide {
         }
     }
 }
+
+discriminant_json_schema!(BoardSide {
+    White,
+    Black,
+    Center
+});
@@ -0,0 +1,118 @@
+use super::{board::Board, player_side::PlayerSide};
+use log::{info, warn};
+use std::{fs, io, path::Path};
+
+pub const FEATURES: usize = 4;
+pub const FEATURE_NAMES: [&str; FEATURES] = ["reserve", "square_threats", "move_up", "free_balls"];
+
+// The unit of the search's scores: a hundredth of a ball in reserve, so that weights of a fraction
+// of a ball still tell positions apart
+pub const BALL: i32 = 100;
+// What a won game is worth to the search
+pub const WIN: i32 = 1000 * BALL;
+
+// What the search thinks of a position it does not look past: a weighted sum of features, each
+// of them the side to move's count minus the opponent's. The weights are in balls.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Evaluation {
+    pub weights: [f64; FEATURES], // In the order of [FEATURE_NAMES]
+}
+
+// Only the balls in reserve count, which is how the search has always judged positions
+impl Default for Evaluation {
+    fn default() -> Self {
+        Evaluation {
+            weights: [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+fn feature(board: &Board, feature: usize) -> f64 {
+    let count = |side: PlayerSide| match feature {
+        0 => board.number_of_balls_in_reserve(side),
+        1 => board.square_threats(side),
+        2 => board.move_up_is_possible(side) as usize,
+        _ => board.free_balls(side),
+    };
+    let me = board.get_turn();
+    count(me) as f64 - count(!me) as f64
+}
+
+pub fn features(board: &Board) -> [f64; FEATURES] {
+    std::array::from_fn(|i| feature(board, i))
+}
+
+impl Evaluation {
+    // Features without weight are not computed, as the search evaluates a lot of positions
+    pub fn score(&self, board: &Board) -> f64 {
+        (0..FEATURES)
+            .filter(|&i| self.weights[i] != 0.0)
+            .map(|i| self.weights[i] * feature(board, i))
+            .sum()
+    }
+
+    // [score] in the search's unit, see [BALL]
+    pub fn value(&self, board: &Board) -> i32 {
+        (self.score(board) * BALL as f64).round() as i32
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    // Tuned weights are optional, so a missing or broken file falls back to the default ones
+    pub fn load_or_default(path: Option<&Path>) -> Self {
+        let Some(path) = path else {
+            return Evaluation::default();
+        };
+        match Evaluation::load(path) {
+            Ok(evaluation) => {
+                info!(
+                    "[evaluation]: weights {:?} from {:?}",
+                    evaluation.weights, path
+                );
+                evaluation
+            }
+            Err(e) => {
+                warn!("[evaluation]: could not load {:?}: {}", path, e);
+                Evaluation::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_of_a_square_threat() {
+        let mut board = Board::new();
+        // White builds three corners of a square while black plays elsewhere
+        let moves = [
+            ((0, 0), (0, 0, 0)),
+            ((0, 0), (3, 3, 0)),
+            ((0, 1), (1, 0, 0)),
+            ((0, 1), (3, 2, 0)),
+            ((0, 2), (0, 1, 0)),
+        ];
+        for (i, &(reserve, to)) in moves.iter().enumerate() {
+            let mv = if i % 2 == 0 {
+                crate::logic::amove::Move::new_wrc(reserve, to)
+            } else {
+                crate::logic::amove::Move::new_brc(reserve, to)
+            };
+            board.make_move(mv).unwrap();
+        }
+
+        // Black to move: one ball more in reserve, and facing white's threat
+        assert_eq!(features(&board), [1.0, -1.0, 0.0, -1.0]);
+        assert_eq!(Evaluation::default().value(&board), BALL);
+        let weights = [0.0, 0.3, 0.0, 0.0];
+        assert_eq!(Evaluation { weights }.value(&board), -30);
+    }
+}
@@ -0,0 +1,85 @@
+use super::{
+    ai::{AI, DEFAULT_FUEL},
+    amove::Move,
+    board::Board,
+    board_side::BoardSide,
+    evaluation::Evaluation,
+};
+
+// Why a hinted move is good, in words a beginner can act on
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq,
+)]
+pub enum HintReason {
+    CompletesSquare = 0,  // The move completes a square, so balls can be taken back
+    BlocksSquare = 1,     // The move fills the spot the opponent needs for a square
+    SavesReserveBall = 2, // A take-back: the ball returns to the reserve
+    MoveUp = 3,           // A ball from the board moves up instead of one from the reserve
+    Positional = 4,       // None of the above; the search simply prefers the move
+}
+
+pub fn hint_reason(board: &Board, mv: Move) -> HintReason {
+    if board.take_back_rule() {
+        return HintReason::SavesReserveBall;
+    }
+    let mut next = board.clone();
+    if next.make_move(mv).is_err() {
+        return HintReason::Positional;
+    }
+    let me = board.get_turn();
+    if next.take_back_rule() {
+        HintReason::CompletesSquare
+    } else if next.square_threats(!me) < board.square_threats(!me) {
+        HintReason::BlocksSquare
+    } else if mv.from.index.b == BoardSide::Center {
+        HintReason::MoveUp
+    } else {
+        HintReason::Positional
+    }
+}
+
+// The move the computer would play in the player's place
+pub fn hint(board: &Board, evaluation: &Evaluation) -> Result<(Move, HintReason), &'static str> {
+    let ai = AI {
+        fuel: DEFAULT_FUEL,
+        board: board.clone(),
+        evaluation: *evaluation,
+        ..AI::new(board.get_turn())
+    };
+    let (_, mv) = ai.search();
+    let mv = mv.ok_or("There are no moves in this position")?;
+    Ok((mv, hint_reason(board, mv)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reasons() {
+        let mut board = Board::new();
+        for mv in [
+            Move::new_wrc((0, 0), (0, 0, 0)),
+            Move::new_brc((0, 0), (3, 3, 0)),
+            Move::new_wrc((1, 0), (1, 0, 0)),
+            Move::new_brc((1, 0), (3, 2, 0)),
+            Move::new_wrc((2, 0), (0, 1, 0)),
+        ] {
+            board.make_move(mv).unwrap();
+        }
+
+        // Black can block white's square, or play elsewhere
+        let block = Move::new_brc((2, 0), (1, 1, 0));
+        assert_eq!(hint_reason(&board, block), HintReason::BlocksSquare);
+        let elsewhere = Move::new_brc((2, 0), (3, 0, 0));
+        assert_eq!(hint_reason(&board, elsewhere), HintReason::Positional);
+
+        // White completes the square, then takes balls back
+        board.make_move(elsewhere).unwrap();
+        let square = Move::new_wrc((3, 0), (1, 1, 0));
+        assert_eq!(hint_reason(&board, square), HintReason::CompletesSquare);
+        board.make_move(square).unwrap();
+        let take_back = board.get_valid_moves()[0];
+        assert_eq!(hint_reason(&board, take_back), HintReason::SavesReserveBall);
+    }
+}
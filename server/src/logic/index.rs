@@ -2,7 +2,9 @@ use std::fmt;
 
 use super::board_side::BoardSide;
 
-#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq,
+)]
 pub struct Index {
     pub b: BoardSide,
     pub x: i8,
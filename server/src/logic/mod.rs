@@ -1,7 +1,32 @@
+// [serde_repr] serializes the enums given to it as their discriminants, which [schemars] cannot
+// derive, so their schemas list the discriminants of the given variants
+macro_rules! discriminant_json_schema {
+    ($name:ident { $($variant:ident),* }) => {
+        impl schemars::JsonSchema for $name {
+            fn schema_name() -> std::borrow::Cow<'static, str> {
+                stringify!($name).into()
+            }
+
+            fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+                schemars::json_schema!({
+                    "type": "integer",
+                    "enum": [$($name::$variant as u8),*]
+                })
+            }
+        }
+    };
+}
+
 pub mod ai;
 pub mod amove;
+pub mod analysis;
+pub mod arena;
 pub mod ball;
 pub mod board;
 pub mod board_side;
+pub mod evaluation;
+pub mod hint;
 pub mod index;
+pub mod opening_book;
 pub mod player_side;
+pub mod training;
@@ -0,0 +1,243 @@
+use super::{ai::AI, amove::Move, board::Board};
+use log::{info, warn};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct BookMove {
+    pub mv: Move,
+    pub weight: u32, // Moves are picked with a probability proportional to their weight
+}
+
+// A line of the book file: the moves leading to a position, and the candidates in it. Storing
+// the line rather than the position keeps the file independent of [Board]'s layout.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+struct BookEntry {
+    line: Vec<Move>,
+    candidates: Vec<BookMove>,
+}
+
+// Positions mapped to the moves worth playing in them; transpositions share an entry
+#[derive(Debug, Default)]
+pub struct OpeningBook {
+    positions: HashMap<Board, BookEntry>,
+}
+
+impl OpeningBook {
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // Replaces the candidates of the position the line leads to
+    pub fn insert(
+        &mut self,
+        line: Vec<Move>,
+        candidates: Vec<BookMove>,
+    ) -> Result<(), &'static str> {
+        let board = Board::replay(&line)?;
+        let is_legal = |book_move: &BookMove| board.clone().make_move(book_move.mv).is_ok();
+        if candidates.is_empty() || !candidates.iter().all(is_legal) {
+            return Err("The book moves are not legal in the position");
+        }
+        self.positions.insert(board, BookEntry { line, candidates });
+        Ok(())
+    }
+
+    pub fn candidates(&self, board: &Board) -> Option<&[BookMove]> {
+        self.positions
+            .get(board)
+            .map(|entry| entry.candidates.as_slice())
+    }
+
+    // A weighted random pick among the position's candidates
+    pub fn choose(&self, board: &Board, rng: &mut impl Rng) -> Option<Move> {
+        let candidates = self.candidates(board)?;
+        let weights =
+            WeightedIndex::new(candidates.iter().map(|book_move| book_move.weight)).ok()?;
+        Some(candidates[weights.sample(rng)].mv)
+    }
+
+    // The book file has one JSON entry per line, shortest lines first
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut entries: Vec<&BookEntry> = self.positions.values().collect();
+        entries.sort_by_key(|entry| entry.line.len());
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        for entry in entries {
+            writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        }
+        file.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut book = OpeningBook::default();
+        for (number, line) in BufReader::new(fs::File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: BookEntry = serde_json::from_str(&line)?;
+            if let Err(e) = book.insert(entry.line, entry.candidates) {
+                warn!("[opening_book]: skipping line {}: {}", number + 1, e);
+            }
+        }
+        Ok(book)
+    }
+
+    // The computer still plays without a book, so a missing or broken file is not fatal
+    pub fn load_or_empty(path: Option<&Path>) -> Self {
+        let Some(path) = path else {
+            return OpeningBook::default();
+        };
+        match OpeningBook::load(path) {
+            Ok(book) => {
+                info!("[opening_book]: {} positions from {:?}", book.len(), path);
+                book
+            }
+            Err(e) => {
+                warn!("[opening_book]: could not load {:?}: {}", path, e);
+                OpeningBook::default()
+            }
+        }
+    }
+}
+
+// How the generator explores the openings
+#[derive(Debug, Clone)]
+pub struct BookSettings {
+    pub depth: usize,      // How many moves deep the book goes
+    pub fuel: i32,         // The search budget for every candidate move
+    pub candidates: usize, // How many moves are kept per position
+    pub margin: i32,       // How much worse than the best move a candidate may score, see [BALL]
+}
+
+// Searches every move of every position reachable through the book's own candidates, and keeps
+// the best few. Better moves get larger weights.
+pub fn generate(settings: &BookSettings) -> OpeningBook {
+    let mut book = OpeningBook::default();
+    let mut frontier: Vec<Vec<Move>> = vec![vec![]];
+
+    for depth in 0..settings.depth {
+        let mut next_frontier = vec![];
+        for line in frontier {
+            let Ok(board) = Board::replay(&line) else {
+                continue;
+            };
+            if board.is_game_over() || book.candidates(&board).is_some() {
+                continue;
+            }
+
+            let ai = AI {
+                fuel: settings.fuel,
+                board: board.clone(),
+                ..AI::new(board.get_turn())
+            };
+            let mut scored = ai.score_moves();
+            scored.sort_by_key(|&(_, score)| -score);
+            let Some(&(_, best)) = scored.first() else {
+                continue;
+            };
+            let candidates: Vec<BookMove> = scored
+                .into_iter()
+                .take(settings.candidates)
+                .filter(|&(_, score)| score >= best - settings.margin)
+                .map(|(mv, score)| BookMove {
+                    mv,
+                    weight: (settings.margin - (best - score) + 1) as u32,
+                })
+                .collect();
+
+            for candidate in &candidates {
+                let mut next_line = line.clone();
+                next_line.push(candidate.mv);
+                next_frontier.push(next_line);
+            }
+            let _ = book.insert(line, candidates);
+        }
+        info!(
+            "[opening_book]: depth {} done, {} positions",
+            depth + 1,
+            book.len()
+        );
+        frontier = next_frontier;
+    }
+    book
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::evaluation::BALL;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn weighted_choice_and_round_trip() {
+        let board = Board::new();
+        let moves = board.get_valid_moves();
+        let mut book = OpeningBook::default();
+        let candidates = vec![
+            BookMove {
+                mv: moves[0],
+                weight: 1,
+            },
+            BookMove {
+                mv: moves[1],
+                weight: 3,
+            },
+        ];
+        book.insert(vec![], candidates.clone()).unwrap();
+        assert!(book
+            .insert(
+                vec![moves[0]],
+                vec![BookMove {
+                    mv: moves[0],
+                    weight: 1
+                }]
+            )
+            .is_err());
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let picks: Vec<Move> = (0..100)
+            .map(|_| book.choose(&board, &mut rng).unwrap())
+            .collect();
+        let second = picks.iter().filter(|&&mv| mv == moves[1]).count();
+        assert!(second > 50 && second < 100, "{}", second);
+
+        let path = std::env::temp_dir().join(format!("book-{}.jsonl", uuid::Uuid::new_v4()));
+        book.save(&path).unwrap();
+        let loaded = OpeningBook::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.candidates(&board), Some(candidates.as_slice()));
+    }
+
+    #[test]
+    fn generated_book_is_consulted() {
+        let settings = BookSettings {
+            depth: 2,
+            fuel: 100,
+            candidates: 2,
+            margin: 10 * BALL,
+        };
+        let book = std::sync::Arc::new(generate(&settings));
+        assert_eq!(book.len(), 3);
+
+        let ai = AI {
+            opening_book: Some(book.clone()),
+            ..AI::new(crate::logic::player_side::PlayerSide::White)
+        };
+        let mv = ai.get_move().unwrap();
+        assert!(book
+            .candidates(&Board::new())
+            .unwrap()
+            .iter()
+            .any(|book_move| book_move.mv == mv));
+    }
+}
@@ -19,3 +19,5 @@ impl Not for PlayerSide {
         }
     }
 }
+
+discriminant_json_schema!(PlayerSide { White, Black });
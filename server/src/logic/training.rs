@@ -0,0 +1,203 @@
+use super::{
+    ai::AI,
+    amove::Move,
+    arena::MAX_MOVES,
+    board::Board,
+    evaluation::{features, Evaluation, FEATURES},
+    player_side::PlayerSide,
+};
+use rand::Rng;
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+
+// A finished self-play game, stored as one JSON line of the training file
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct SelfPlayGame {
+    pub moves: Vec<Move>,
+    pub winner: Option<PlayerSide>, // None if the game hit [MAX_MOVES]
+}
+
+#[derive(Debug, Clone)]
+pub struct SelfPlaySettings {
+    pub fuel: i32,
+    pub evaluation: Evaluation,
+    pub opening_moves: usize, // Random moves played before the engine takes over
+    pub exploration: f64,     // The probability of a random move afterwards
+}
+
+pub fn self_play_game(settings: &SelfPlaySettings, rng: &mut impl Rng) -> SelfPlayGame {
+    let mut board = Board::new();
+    let mut moves = vec![];
+    while !board.is_game_over() && moves.len() < MAX_MOVES {
+        let valid_moves = board.get_valid_moves();
+        if valid_moves.is_empty() {
+            break;
+        }
+        let explore = moves.len() < settings.opening_moves || rng.gen_bool(settings.exploration);
+        let mv = if explore {
+            valid_moves[rng.gen_range(0..valid_moves.len())]
+        } else {
+            let ai = AI {
+                side: board.get_turn(),
+                board: board.clone(),
+                fuel: settings.fuel,
+                evaluation: settings.evaluation,
+                opening_book: None,
+            };
+            match ai.search() {
+                (_, Some(mv)) => mv,
+                (_, None) => break,
+            }
+        };
+        if board.make_move(mv).is_err() {
+            break;
+        }
+        moves.push(mv);
+    }
+    SelfPlayGame {
+        moves,
+        winner: board.get_winner(),
+    }
+}
+
+pub fn save_games(games: &[SelfPlayGame], path: &Path) -> io::Result<()> {
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+    for game in games {
+        writeln!(file, "{}", serde_json::to_string(game)?)?;
+    }
+    file.flush()
+}
+
+pub fn load_games(path: &Path) -> io::Result<Vec<SelfPlayGame>> {
+    let mut games = vec![];
+    for line in BufReader::new(fs::File::open(path)?).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            games.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(games)
+}
+
+// A position's features and how the game ended for the side to move: 1 for a win, 0.5 for a
+// draw and 0 for a loss
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub features: [f64; FEATURES],
+    pub outcome: f64,
+}
+
+// Positions in the middle of a take-back are skipped, as the evaluation only ever sees them
+// after the side has finished its turn
+pub fn samples(game: &SelfPlayGame) -> Vec<Sample> {
+    let mut samples = vec![];
+    let mut board = Board::new();
+    for &mv in &game.moves {
+        if !board.take_back_rule() {
+            let outcome = match game.winner {
+                Some(winner) if winner == board.get_turn() => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            samples.push(Sample {
+                features: features(&board),
+                outcome,
+            });
+        }
+        if board.make_move(mv).is_err() {
+            break;
+        }
+    }
+    samples
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn predict(evaluation: &Evaluation, scale: f64, sample: &Sample) -> f64 {
+    let score: f64 = (0..FEATURES)
+        .map(|i| evaluation.weights[i] * sample.features[i])
+        .sum();
+    sigmoid(scale * score)
+}
+
+// The mean squared difference between the predicted and the actual outcomes
+pub fn error(evaluation: &Evaluation, scale: f64, samples: &[Sample]) -> f64 {
+    let total: f64 = samples
+        .iter()
+        .map(|sample| (predict(evaluation, scale, sample) - sample.outcome).powi(2))
+        .sum();
+    total / samples.len().max(1) as f64
+}
+
+// The scale that turns the evaluation into a winning probability best. It is fitted once, for
+// the starting weights, so that the tuned weights stay in the same unit: balls in reserve.
+pub fn fit_scale(evaluation: &Evaluation, samples: &[Sample]) -> f64 {
+    (1..=400)
+        .map(|i| i as f64 / 100.0)
+        .min_by(|&a, &b| error(evaluation, a, samples).total_cmp(&error(evaluation, b, samples)))
+        .unwrap()
+}
+
+// Texel tuning: gradient descent on [error], starting from the given weights
+pub fn tune(
+    evaluation: &Evaluation,
+    scale: f64,
+    samples: &[Sample],
+    iterations: usize,
+    learning_rate: f64,
+) -> Evaluation {
+    let mut evaluation = *evaluation;
+    let n = samples.len().max(1) as f64;
+    for _ in 0..iterations {
+        let mut gradient = [0.0; FEATURES];
+        for sample in samples {
+            let prediction = predict(&evaluation, scale, sample);
+            let slope = 2.0 * (prediction - sample.outcome) * prediction * (1.0 - prediction);
+            for (g, feature) in gradient.iter_mut().zip(sample.features) {
+                *g += slope * scale * feature / n;
+            }
+        }
+        for (weight, g) in evaluation.weights.iter_mut().zip(gradient) {
+            *weight -= learning_rate * g;
+        }
+    }
+    evaluation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn self_play_and_tuning() {
+        let settings = SelfPlaySettings {
+            fuel: 100,
+            evaluation: Evaluation::default(),
+            opening_moves: 4,
+            exploration: 0.1,
+        };
+        let mut rng = StdRng::seed_from_u64(3);
+        let games: Vec<SelfPlayGame> = (0..4)
+            .map(|_| self_play_game(&settings, &mut rng))
+            .collect();
+
+        let path = std::env::temp_dir().join(format!("self-play-{}.jsonl", uuid::Uuid::new_v4()));
+        save_games(&games, &path).unwrap();
+        let loaded = load_games(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, games);
+
+        let samples: Vec<Sample> = games.iter().flat_map(samples).collect();
+        assert!(!samples.is_empty());
+        let scale = fit_scale(&settings.evaluation, &samples);
+        let before = error(&settings.evaluation, scale, &samples);
+        let tuned = tune(&settings.evaluation, scale, &samples, 100, 1.0);
+        assert!(error(&tuned, scale, &samples) <= before);
+    }
+}
@@ -1,19 +1,29 @@
 use http::{Method, StatusCode};
-use log::info;
+use log::{info, warn};
 use pylos::{
     protocol::{
-        html::{RegisterRequest, RegisterResponse},
+        handshake::protocol_schema,
+        html::{
+            BotRegisterRequest, BotRegisterResponse, LeaderboardQuery, RegisterRequest,
+            RegisterResponse,
+        },
         result::Result,
+        stream::{bot_request_handler, bot_stream_handler},
         ws::ws_handler,
     },
     state::{
+        bot::is_operator,
         client::{Client, Clients},
-        game::Games,
+        janitor::run_janitor,
+        matchmaking::run_matchmaker,
+        server_config::ServerConfig,
+        server_state::ServerState,
+        stats::{completed_games, leaderboard, user_stats},
+        tournament::run_tournaments,
         user_uuid::UserUUID,
     },
 };
-use std::{collections::HashMap, convert::Infallible, sync::Arc};
-use tokio::sync::Mutex;
+use std::convert::Infallible;
 use warp::{
     reply::{json, Reply},
     Filter,
@@ -24,22 +34,48 @@ pub async fn health_handler() -> Result<impl Reply> {
     Ok(StatusCode::OK)
 }
 
-pub async fn register_handler(body: RegisterRequest, clients: Clients) -> Result<impl Reply> {
+pub async fn schema_handler() -> Result<impl Reply> {
+    info!("[schema_handler]");
+    Ok(json(&protocol_schema()))
+}
+
+pub async fn register_handler(body: RegisterRequest, state: ServerState) -> Result<impl Reply> {
     info!("[register_handler]: {:?} ", body);
+    if state.bots.read().await.is_bot(&body.user_uuid) {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
 
-    clients.lock().await.insert(
+    state.clients.lock().await.insert(
         body.user_uuid.clone(),
         Client {
             user_name: body.user_name,
             user_uuid: body.user_uuid.clone(),
             user_avatar_uuid: body.user_avatar_uuid,
+            capabilities: vec![],
             sender: None,
         },
     );
 
     Ok(json(&RegisterResponse {
         url: body.user_uuid.to_string(),
-    }))
+    })
+    .into_response())
+}
+
+// Bots connect like other clients once registered, showing their token
+pub async fn bot_register_handler(
+    authorization: Option<String>,
+    body: BotRegisterRequest,
+    state: ServerState,
+) -> Result<impl Reply> {
+    info!("[bot_register_handler]: {:?}", body);
+    let operator_token = state.config.bot_operator_token.as_deref();
+    if let Err(e) = is_operator(operator_token, authorization.as_deref()) {
+        warn!("[bot_register_handler]: {}", e);
+        return Ok(StatusCode::UNAUTHORIZED.into_response());
+    }
+    let (user_uuid, token) = state.bots.write().await.register(body.user_name);
+    Ok(json(&BotRegisterResponse { user_uuid, token }).into_response())
 }
 
 pub async fn unregister_handler(id: String, clients: Clients) -> Result<impl Reply> {
@@ -48,12 +84,43 @@ pub async fn unregister_handler(id: String, clients: Clients) -> Result<impl Rep
     Ok(StatusCode::OK)
 }
 
+pub async fn leaderboard_handler(
+    query: LeaderboardQuery,
+    state: ServerState,
+) -> Result<impl Reply> {
+    info!("[leaderboard_handler]: {:?}", query);
+    let mut entries = leaderboard(
+        &*state.ratings.read().await,
+        query.pool,
+        query.sort,
+        query.limit,
+    );
+    let clients = state.clients.lock().await;
+    for entry in &mut entries {
+        entry.user_name = clients
+            .get(&entry.user_uuid)
+            .map(|client| client.user_name.clone());
+    }
+    Ok(json(&entries))
+}
+
+pub async fn stats_handler(id: String, state: ServerState) -> Result<impl Reply> {
+    info!("[stats_handler]: {:?}", id);
+    let Ok(user_uuid) = UserUUID::new(id) else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+    let records = completed_games(&state).await;
+    Ok(json(&user_stats(&user_uuid, &records)).into_response())
+}
+
 fn with_clients(clients: Clients) -> impl Filter<Extract = (Clients,), Error = Infallible> + Clone {
     warp::any().map(move || clients.clone())
 }
 
-fn with_games(games: Games) -> impl Filter<Extract = (Games,), Error = Infallible> + Clone {
-    warp::any().map(move || games.clone())
+fn with_state(
+    state: ServerState,
+) -> impl Filter<Extract = (ServerState,), Error = Infallible> + Clone {
+    warp::any().map(move || state.clone())
 }
 
 #[tokio::main]
@@ -61,18 +128,26 @@ async fn main() {
     std::env::set_var("RUST_LOG", "info");
     env_logger::init();
 
-    let clients: Clients = Arc::new(Mutex::new(HashMap::new()));
-    let games: Games = Arc::new(Mutex::new(HashMap::new()));
+    let state = ServerState::new(ServerConfig::from_env());
+    let clients = state.clients.clone();
+
+    tokio::spawn(run_janitor(state.clone()));
+    tokio::spawn(run_matchmaker(state.clone()));
+    tokio::spawn(run_tournaments(state.clone()));
 
     let health_route = warp::path("health")
         .and(warp::post())
         .and_then(health_handler);
 
+    let schema_route = warp::path("schema")
+        .and(warp::get())
+        .and_then(schema_handler);
+
     let users = warp::path("clients");
     let users_routes = users
         .and(warp::post())
         .and(warp::body::json())
-        .and(with_clients(clients.clone()))
+        .and(with_state(state.clone()))
         .and_then(register_handler)
         .or(users
             .and(warp::delete())
@@ -80,11 +155,41 @@ async fn main() {
             .and(with_clients(clients.clone()))
             .and_then(unregister_handler));
 
+    let leaderboard_route = warp::path("leaderboard")
+        .and(warp::get())
+        .and(warp::query())
+        .and(with_state(state.clone()))
+        .and_then(leaderboard_handler);
+
+    let stats_route = warp::path!("clients" / String / "stats")
+        .and(warp::get())
+        .and(with_state(state.clone()))
+        .and_then(stats_handler);
+
+    let bot_routes = warp::path!("bots")
+        .and(warp::post())
+        .and(warp::header::optional("authorization"))
+        .and(warp::body::json())
+        .and(with_state(state.clone()))
+        .and_then(bot_register_handler)
+        .or(warp::path!("bots" / UserUUID / "stream")
+            .and(warp::get())
+            .and(warp::header::optional("authorization"))
+            .and(with_state(state.clone()))
+            .and_then(bot_stream_handler))
+        .or(warp::path!("bots" / UserUUID / "requests")
+            .and(warp::post())
+            .and(warp::header::optional("authorization"))
+            .and(warp::body::content_length_limit(64 * 1024))
+            .and(warp::body::bytes())
+            .and(with_state(state.clone()))
+            .and_then(bot_request_handler));
+
     let ws_route = warp::path("ws")
         .and(warp::ws())
         .and(warp::path::param())
-        .and(with_clients(clients.clone()))
-        .and(with_games(games.clone()))
+        .and(warp::header::optional("authorization"))
+        .and(with_state(state))
         .and_then(ws_handler);
 
     let static_files = warp::path("static").and(warp::fs::dir("static"));
@@ -99,6 +204,7 @@ async fn main() {
             "User-Agent",
             "Content-Type",
             "Access-Control-Allow-Origin",
+            "Authorization",
         ])
         .allow_methods(&[
             Method::POST,
@@ -111,7 +217,11 @@ async fn main() {
         .build();
 
     let routes = health_route
+        .or(schema_route)
         .or(users_routes)
+        .or(leaderboard_route)
+        .or(stats_route)
+        .or(bot_routes)
         .or(ws_route)
         .or(static_files)
         .or(models)
@@ -0,0 +1,120 @@
+use crate::{
+    protocol::response::{Response, ResponseEnvelope},
+    state::{client::ClientSender, game_uuid::GameUUID, lobby::LobbyFilter, user_uuid::UserUUID},
+};
+use log::warn;
+use std::collections::HashMap;
+use tokio::{
+    spawn,
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
+};
+use warp::filters::ws::Message;
+
+// A client's WebSocket connection together with the games whose events it receives.
+// Subscriptions live as long as the connection; a reconnecting client has to subscribe again.
+pub struct Connection {
+    pub client_uuid: UserUUID,
+    sender: ClientSender,
+    subscriptions: HashMap<GameUUID, JoinHandle<()>>,
+    lobby_subscription: Option<JoinHandle<()>>,
+}
+
+impl Connection {
+    pub fn new(client_uuid: UserUUID, sender: ClientSender) -> Self {
+        Connection {
+            client_uuid,
+            sender,
+            subscriptions: HashMap::new(),
+            lobby_subscription: None,
+        }
+    }
+
+    pub fn send(&self, res: &ResponseEnvelope) {
+        let _ = self
+            .sender
+            .send(Ok(Message::text(serde_json::to_string(res).unwrap())));
+    }
+
+    pub fn close(&self) {
+        let _ = self.sender.send(Ok(Message::close()));
+    }
+
+    pub fn subscribed_games(&self) -> impl Iterator<Item = &GameUUID> {
+        self.subscriptions.keys()
+    }
+
+    // Forwards the game's events to the client; subscribing twice to the same game is a no-op
+    pub fn subscribe(&mut self, game_uuid: GameUUID, events: broadcast::Receiver<Response>) {
+        if self.subscriptions.contains_key(&game_uuid) {
+            return;
+        }
+        let forwarder = self.forward(events, Some);
+        self.subscriptions.insert(game_uuid, forwarder);
+    }
+
+    pub fn unsubscribe(&mut self, game_uuid: &GameUUID) {
+        if let Some(forwarder) = self.subscriptions.remove(game_uuid) {
+            forwarder.abort();
+        }
+    }
+
+    // Forwards the lobby's events about games matching [filter], replacing any previous
+    // lobby subscription. Updates of games that do not match are sent as removals, so that
+    // the client drops games that stopped matching.
+    pub fn subscribe_lobby(&mut self, events: broadcast::Receiver<Response>, filter: LobbyFilter) {
+        self.unsubscribe_lobby();
+        let forwarder = self.forward(events, move |res| match res {
+            Response::GameAdded { game } if !filter.matches(&game) => None,
+            Response::GameUpdated { game } if !filter.matches(&game) => {
+                Some(Response::GameRemoved { game_uuid: game.0 })
+            }
+            res => Some(res),
+        });
+        self.lobby_subscription = Some(forwarder);
+    }
+
+    pub fn unsubscribe_lobby(&mut self) {
+        if let Some(forwarder) = self.lobby_subscription.take() {
+            forwarder.abort();
+        }
+    }
+
+    // Spawns a task sending the events that [map] keeps to the client
+    fn forward(
+        &self,
+        mut events: broadcast::Receiver<Response>,
+        map: impl Fn(Response) -> Option<Response> + Send + 'static,
+    ) -> JoinHandle<()> {
+        let sender = self.sender.clone();
+        let client_uuid = self.client_uuid.clone();
+        spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(res) => {
+                        let Some(res) = map(res) else {
+                            continue;
+                        };
+                        let msg = Message::text(serde_json::to_string(&res).unwrap());
+                        if sender.send(Ok(msg)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(n)) => {
+                        warn!("[subscription]: {} missed {} events", client_uuid, n);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.subscriptions
+            .values()
+            .for_each(|forwarder| forwarder.abort());
+        self.unsubscribe_lobby();
+    }
+}
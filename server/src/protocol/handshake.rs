@@ -0,0 +1,72 @@
+use super::{request::RequestEnvelope, response::ResponseEnvelope};
+use schemars::schema_for;
+
+// Bumped on every breaking change to [Request] or [Response]
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Optional protocol features; a client only receives messages of the capabilities it asked for
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq,
+)]
+pub enum Capability {
+    Acks = 0, // [Ack] and [Error] replies to requests with a [request_id]
+}
+
+pub const SUPPORTED_CAPABILITIES: &[Capability] = &[Capability::Acks];
+
+pub fn negotiate(
+    protocol_version: u32,
+    capabilities: &[Capability],
+) -> Result<Vec<Capability>, String> {
+    if protocol_version != PROTOCOL_VERSION {
+        return Err(format!(
+            "Unsupported protocol version {}, the server speaks version {}",
+            protocol_version, PROTOCOL_VERSION
+        ));
+    }
+    Ok(capabilities
+        .iter()
+        .filter(|&capability| SUPPORTED_CAPABILITIES.contains(capability))
+        .copied()
+        .collect())
+}
+
+pub fn protocol_schema() -> serde_json::Value {
+    serde_json::json!({
+        "protocol_version": PROTOCOL_VERSION,
+        "capabilities": SUPPORTED_CAPABILITIES,
+        "request": schema_for!(RequestEnvelope),
+        "response": schema_for!(ResponseEnvelope),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_mismatch() {
+        assert!(negotiate(PROTOCOL_VERSION + 1, &[Capability::Acks]).is_err());
+        assert_eq!(
+            negotiate(PROTOCOL_VERSION, &[Capability::Acks]),
+            Ok(vec![Capability::Acks])
+        );
+    }
+
+    #[test]
+    fn schema_covers_requests() {
+        let schema = protocol_schema().to_string();
+        assert!(schema.contains("MakeMove"));
+        assert!(schema.contains("request_id"));
+    }
+
+    #[test]
+    fn sides_are_described_by_their_discriminants() {
+        use crate::logic::{board_side::BoardSide, player_side::PlayerSide};
+        let board_side = schema_for!(BoardSide);
+        assert_eq!(board_side.as_value()["type"], "integer");
+        assert_eq!(board_side.as_value()["enum"], serde_json::json!([0, 1, 2]));
+        let player_side = schema_for!(PlayerSide);
+        assert_eq!(player_side.as_value()["enum"], serde_json::json!([0, 1]));
+    }
+}
@@ -1,4 +1,8 @@
-use crate::state::user_uuid::UserUUID;
+use crate::state::{
+    game_configuration::TimeControlCategory,
+    stats::{LeaderboardSort, MAX_LEADERBOARD_SIZE},
+    user_uuid::UserUUID,
+};
 
 #[derive(serde::Deserialize, serde::Serialize, Debug)]
 pub struct RegisterRequest {
@@ -11,3 +15,28 @@ pub struct RegisterRequest {
 pub struct RegisterResponse {
     pub url: String,
 }
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct BotRegisterRequest {
+    pub user_name: String,
+}
+
+// The token is only sent once; the bot authenticates with it from then on
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct BotRegisterResponse {
+    pub user_uuid: UserUUID,
+    pub token: String,
+}
+
+fn default_leaderboard_size() -> usize {
+    MAX_LEADERBOARD_SIZE
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct LeaderboardQuery {
+    pub pool: TimeControlCategory,
+    #[serde(default)]
+    pub sort: LeaderboardSort,
+    #[serde(default = "default_leaderboard_size")]
+    pub limit: usize,
+}
@@ -1,5 +1,8 @@
+pub mod connection;
+pub mod handshake;
 pub mod html;
 pub mod request;
 pub mod response;
 pub mod result;
+pub mod stream;
 pub mod ws;
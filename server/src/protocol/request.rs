@@ -1,22 +1,106 @@
+use super::handshake::Capability;
 use crate::{
-    logic::amove::Move,
-    state::{game_configuration::GameConfiguration, game_uuid::GameUUID},
+    logic::{amove::Move, analysis::AnalysisLimit},
+    state::{
+        challenge::ChallengeUUID,
+        game::Role,
+        game_configuration::{GameConfiguration, TimeControl},
+        game_uuid::GameUUID,
+        lobby::{LobbyFilter, LobbyQuery},
+        matchmaking::MatchRules,
+        tournament::{TournamentConfiguration, TournamentUUID},
+        user_uuid::UserUUID,
+    },
 };
 
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub type RequestId = String;
+
+// The position to analyze: the moves leading to it, or a completed game after its first
+// [move_number] moves. The current position of a game the caller is playing is refused.
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug)]
+pub enum AnalysisTarget {
+    Position {
+        moves: Vec<Move>,
+    },
+    Game {
+        game_uuid: GameUUID,
+        move_number: usize,
+    },
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug)]
 pub enum Request {
+    Hello {
+        protocol_version: u32,
+        capabilities: Vec<Capability>,
+    },
+
     ChangeProfileInfo {
         new_user_name: String,
         new_user_avatar: String,
     },
+    GetProfile {
+        #[serde(default)]
+        user_uuid: Option<UserUUID>, // The client's own profile, if left out
+    },
 
     CreateGame {
         game_configuration: GameConfiguration,
     },
     JoinGame {
         game_uuid: GameUUID,
+        #[serde(default)]
+        role: Option<Role>, // Take a free seat or else watch, if left out
+    },
+    LeaveGame {
+        game_uuid: GameUUID,
+    },
+    GetAvailableGames {
+        #[serde(flatten)]
+        query: LobbyQuery, // Every game, newest first, if left out
+    },
+    SubscribeLobby {
+        #[serde(default)]
+        filter: LobbyFilter,
+    },
+    UnsubscribeLobby {},
+    FindMatch {
+        time_control: Option<TimeControl>, // None if unlimited
+        #[serde(default)]
+        rules: MatchRules,
     },
-    GetAvailableGames {},
+    CancelMatch {},
+    Challenge {
+        target_user_uuid: UserUUID, // Has to be online
+        game_configuration: GameConfiguration,
+    },
+    AcceptChallenge {
+        challenge_uuid: ChallengeUUID,
+    },
+    DeclineChallenge {
+        challenge_uuid: ChallengeUUID,
+    },
+    CancelChallenge {
+        challenge_uuid: ChallengeUUID, // Only the challenger can cancel it
+    },
+    GetChallenges {}, // The pending challenges the client has sent or received
+    CreateTournament {
+        configuration: TournamentConfiguration,
+    },
+    GetTournaments {},
+    GetTournament {
+        tournament_uuid: TournamentUUID,
+    },
+    JoinTournament {
+        tournament_uuid: TournamentUUID,
+    },
+    LeaveTournament {
+        tournament_uuid: TournamentUUID,
+    },
+    StartTournament {
+        tournament_uuid: TournamentUUID, // Only the creator can start it
+    },
+    Reconnect {},
     GetGameState {
         game_uuid: GameUUID,
     },
@@ -25,4 +109,101 @@ pub enum Request {
         game_uuid: GameUUID,
         mv: Move,
     },
+    PreviewMove {
+        game_uuid: GameUUID,
+        mv: Move, // Checked against the rules, but not played
+    },
+
+    Resign {
+        game_uuid: GameUUID,
+    },
+    OfferDraw {
+        game_uuid: GameUUID,
+    },
+    AcceptDraw {
+        game_uuid: GameUUID,
+    },
+    DeclineDraw {
+        game_uuid: GameUUID,
+    },
+    Abort {
+        game_uuid: GameUUID,
+    },
+
+    RequestTakeback {
+        game_uuid: GameUUID,
+    },
+    AcceptTakeback {
+        game_uuid: GameUUID,
+    },
+    DeclineTakeback {
+        game_uuid: GameUUID,
+    },
+
+    SendChat {
+        game_uuid: GameUUID,
+        text: String,
+    },
+    RequestHint {
+        game_uuid: GameUUID, // Casual games only, on the player's turn
+    },
+
+    AnalyzePosition {
+        position: AnalysisTarget,
+        #[serde(default)]
+        limit: AnalysisLimit,
+        #[serde(default)]
+        lines: Option<usize>, // How many moves to score, 3 if left out
+    },
+    GetGameAnalysis {
+        game_uuid: GameUUID, // A completed game
+    },
+}
+
+// A [Request] with an optional client-supplied [request_id], which is echoed back in the reply.
+// Since [request_id] is optional and the request is flattened, a bare [Request] is a valid envelope.
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug)]
+pub struct RequestEnvelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<RequestId>,
+    #[serde(flatten)]
+    pub request: Request,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::lobby::LobbySort;
+
+    #[test]
+    fn bare_request() {
+        let env: RequestEnvelope =
+            serde_json::from_str(r#"{"JoinGame":{"game_uuid":"abc"}}"#).unwrap();
+        assert!(env.request_id.is_none());
+        assert!(
+            matches!(env.request, Request::JoinGame { game_uuid, role: None } if game_uuid == "abc")
+        );
+    }
+
+    #[test]
+    fn enveloped_request() {
+        let env: RequestEnvelope =
+            serde_json::from_str(r#"{"request_id":"42","GetAvailableGames":{}}"#).unwrap();
+        assert_eq!(env.request_id.as_deref(), Some("42"));
+        assert!(matches!(env.request, Request::GetAvailableGames { .. }));
+    }
+
+    #[test]
+    fn lobby_query() {
+        let env: RequestEnvelope = serde_json::from_str(
+            r#"{"GetAvailableGames":{"filter":{"has_free_seat":true},"sort":"Oldest","limit":20}}"#,
+        )
+        .unwrap();
+        let Request::GetAvailableGames { query } = env.request else {
+            panic!("Expected GetAvailableGames");
+        };
+        assert_eq!(query.filter.has_free_seat, Some(true));
+        assert!(matches!(query.sort, LobbySort::Oldest));
+        assert_eq!((query.offset, query.limit), (0, Some(20)));
+    }
 }
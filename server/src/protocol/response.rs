@@ -1,18 +1,45 @@
+use super::{handshake::Capability, request::RequestId};
 use crate::{
-    logic::board::BoardFrontend,
+    logic::{
+        amove::Move,
+        analysis::{GameAnnotation, PositionAnalysis},
+        board::BoardFrontend,
+        hint::HintReason,
+        player_side::PlayerSide,
+    },
     state::{
-        game::Player, game_configuration::GameConfiguration, game_meta::GameMeta,
+        challenge::{Challenge, ChallengeUUID},
+        chat::ChatEntry,
+        clock::ClockState,
+        game::Player,
+        game_configuration::TimeControlCategory,
+        game_meta::GameResult,
         game_uuid::GameUUID,
+        lobby::GameSummary,
+        rating::{PlayerRating, Rating},
+        tournament::{Standing, Tournament, TournamentPairing, TournamentStatus, TournamentUUID},
+        user_uuid::UserUUID,
     },
 };
+use std::collections::HashMap;
 
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone)]
 pub enum Response {
+    Hello {
+        protocol_version: u32,
+        capabilities: Vec<Capability>, // The subset of the requested capabilities the server supports
+    },
+
     ChangeProfileInfo {
         status: u8,
         user_name: String,
         user_avatar: String,
     },
+    Profile {
+        user_uuid: UserUUID,
+        user_name: Option<String>, // None if the user is not registered
+        ratings: HashMap<TimeControlCategory, Rating>, // Only the pools the user has played in
+    },
 
     CreateGame {
         status: u8,
@@ -23,14 +50,158 @@ pub enum Response {
         game_uuid: GameUUID,
         player_white: Option<(String, String, Player)>, // Name, AvatarUUID, Player
         player_black: Option<(String, String, Player)>, // Name, AvatarUUID, Player
+        rating_white: Option<PlayerRating>,             // None for the computer
+        rating_black: Option<PlayerRating>,
+        spectators: usize,
     },
 
     AvailableGames {
-        available_games: Vec<(GameUUID, GameMeta, GameConfiguration)>,
+        available_games: Vec<GameSummary>,
+        total: usize, // The number of games matching the filter, across all pages
+    },
+    // Sent to clients watching the lobby. A game that no longer matches a client's filter is
+    // reported to that client as removed.
+    GameAdded {
+        game: GameSummary,
+    },
+    GameUpdated {
+        game: GameSummary,
+    },
+    GameRemoved {
+        game_uuid: GameUUID,
+    },
+
+    MatchFound {
+        game_uuid: GameUUID, // The game has started; [JoinGame] subscribes to its events
+        side: PlayerSide,
+    },
+
+    // Sent to the challenger in reply, and to the target
+    Challenge {
+        challenge: Challenge,
+    },
+    Challenges {
+        challenges: Vec<Challenge>,
+    },
+    // Sent to both users. The private game has started with both seated; [JoinGame] subscribes
+    // to its events.
+    ChallengeAccepted {
+        challenge_uuid: ChallengeUUID,
+        game_uuid: GameUUID,
+    },
+    ChallengeDeclined {
+        challenge_uuid: ChallengeUUID, // Sent to the challenger
+    },
+    ChallengeCanceled {
+        challenge_uuid: ChallengeUUID, // Sent to the target
+    },
+    ChallengeExpired {
+        challenge_uuid: ChallengeUUID, // Sent to both users
+    },
+
+    TournamentCreated {
+        tournament_uuid: TournamentUUID,
+    },
+    Tournaments {
+        tournaments: Vec<Tournament>,
+    },
+    Tournament {
+        tournament: Tournament,
+        standings: Vec<Standing>, // Best first
+    },
+    // Sent to the participants. Each round's games have started with both players seated by
+    // the time [RoundStarted] arrives; [JoinGame] subscribes to their events.
+    RoundStarted {
+        tournament_uuid: TournamentUUID,
+        round: usize, // Starting at 1
+        pairings: Vec<TournamentPairing>,
+    },
+    TournamentStandings {
+        tournament_uuid: TournamentUUID,
+        round: usize, // The number of rounds played so far
+        status: TournamentStatus,
+        standings: Vec<Standing>, // Best first
     },
 
     GameState {
         game_uuid: GameUUID,
         game_state: BoardFrontend,
+        clock: Option<ClockState>, // None if unlimited
+        legal_moves: Vec<Move>,    // For the side to move
+    },
+    // The position a move would lead to; the game itself is left as it is
+    MovePreview {
+        game_uuid: GameUUID,
+        mv: Move,
+        position: BoardFrontend,
+        starts_take_back: bool, // Whether the move completes a square
+        legal_moves: Vec<Move>, // In the resulting position
+    },
+
+    GameOver {
+        game_uuid: GameUUID,
+        result: GameResult,
+    },
+    Presence {
+        game_uuid: GameUUID,
+        side: PlayerSide,
+        online: bool,
+    },
+    Reconnected {
+        game_uuids: Vec<GameUUID>, // The games the client was subscribed to again
+    },
+    DrawOffered {
+        game_uuid: GameUUID,
+        side: PlayerSide,
     },
+    DrawDeclined {
+        game_uuid: GameUUID,
+        side: PlayerSide,
+    },
+    TakebackRequested {
+        game_uuid: GameUUID,
+        side: PlayerSide,
+    },
+    TakebackDeclined {
+        game_uuid: GameUUID,
+        side: PlayerSide,
+    },
+    Hint {
+        game_uuid: GameUUID,
+        mv: Move,
+        reason: HintReason,
+    },
+    ChatMessage {
+        game_uuid: GameUUID,
+        message: ChatEntry,
+    },
+    ChatHistory {
+        game_uuid: GameUUID,
+        messages: Vec<ChatEntry>, // Oldest first, from the channels the recipient may read
+    },
+
+    Analysis {
+        position: BoardFrontend,
+        analysis: PositionAnalysis,
+    },
+    GameAnalysis {
+        game_uuid: GameUUID,
+        annotation: GameAnnotation,
+    },
+
+    // Sent only for requests with a [request_id] or to clients with the [Acks] capability.
+    // A failed [Hello] is answered with an [Error] regardless, and the connection is closed.
+    Ack {},
+    Error {
+        message: String,
+    },
+}
+
+// A [Response] with the [request_id] of the request it answers, [None] for broadcasts.
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug)]
+pub struct ResponseEnvelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<RequestId>,
+    #[serde(flatten)]
+    pub response: Response,
 }
@@ -0,0 +1,124 @@
+use crate::{
+    protocol::{result::Result, ws::client_connection},
+    state::{
+        bot::{bearer_token, BotAccount},
+        client::Client,
+        server_state::ServerState,
+        user_uuid::UserUUID,
+    },
+};
+use futures::{future::ready, StreamExt};
+use http::{
+    header::{HeaderValue, CONTENT_TYPE},
+    StatusCode,
+};
+use log::{info, warn};
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use warp::{
+    filters::ws::Message,
+    hyper::{body::Bytes, Body},
+    reply::{self, Reply},
+};
+
+pub(crate) async fn authenticate_bot(
+    user_uuid: &UserUUID,
+    authorization: Option<&str>,
+    state: &ServerState,
+) -> std::result::Result<Client, StatusCode> {
+    state
+        .bots
+        .read()
+        .await
+        .authenticate(user_uuid, bearer_token(authorization))
+        .map(BotAccount::client)
+        .map_err(|e| {
+            warn!("[authenticate_bot]: {}: {}", user_uuid, e);
+            StatusCode::UNAUTHORIZED
+        })
+}
+
+// For bots that cannot hold a WebSocket: the responses and game events the socket would carry,
+// one JSON object per line, for as long as the bot keeps the response open. Its requests are
+// posted to [bot_request_handler].
+pub async fn bot_stream_handler(
+    user_uuid: UserUUID,
+    authorization: Option<String>,
+    state: ServerState,
+) -> Result<reply::Response> {
+    info!("[bot_stream_handler]: {}", user_uuid);
+    let client = match authenticate_bot(&user_uuid, authorization.as_deref(), &state).await {
+        Ok(client) => client,
+        Err(status) => return Ok(status.into_response()),
+    };
+    let (requests, inbound) = mpsc::unbounded_channel();
+    let (client_sender, client_rcv) = mpsc::unbounded_channel();
+    if let Err(e) =
+        state
+            .bots
+            .write()
+            .await
+            .open_stream(user_uuid.clone(), requests, client_sender.clone())
+    {
+        return Ok(reply::with_status(e, StatusCode::CONFLICT).into_response());
+    }
+
+    tokio::spawn(async move {
+        let stream_sender = client_sender.clone();
+        let inbound = UnboundedReceiverStream::new(inbound);
+        client_connection(
+            user_uuid.clone(),
+            state.clone(),
+            client,
+            client_sender,
+            inbound,
+        )
+        .await;
+        state
+            .bots
+            .write()
+            .await
+            .close_stream(&user_uuid, &stream_sender);
+    });
+
+    // The stream ends when the connection is closed, like a WebSocket would be
+    let events = UnboundedReceiverStream::new(client_rcv)
+        .take_while(|msg| ready(msg.as_ref().is_ok_and(|msg| !msg.is_close())))
+        .filter_map(|msg| {
+            ready(
+                msg.ok()
+                    .and_then(|msg| msg.to_str().map(|text| format!("{}\n", text)).ok())
+                    .map(Ok::<_, Infallible>),
+            )
+        });
+    let mut response = reply::Response::new(Body::wrap_stream(events));
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/x-ndjson"),
+    );
+    Ok(response)
+}
+
+// A request of a bot listening to its stream; the response arrives on the stream
+pub async fn bot_request_handler(
+    user_uuid: UserUUID,
+    authorization: Option<String>,
+    body: Bytes,
+    state: ServerState,
+) -> Result<reply::Response> {
+    info!("[bot_request_handler]: {}", user_uuid);
+    if let Err(status) = authenticate_bot(&user_uuid, authorization.as_deref(), &state).await {
+        return Ok(status.into_response());
+    }
+    let Ok(text) = String::from_utf8(body.to_vec()) else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+    let Some(requests) = state.bots.read().await.stream(&user_uuid) else {
+        return Ok(
+            reply::with_status("Open the event stream first", StatusCode::CONFLICT).into_response(),
+        );
+    };
+    let _ = requests.send(Message::text(text));
+    Ok(StatusCode::ACCEPTED.into_response())
+}
@@ -1,21 +1,44 @@
 use crate::{
-    logic::amove::Move,
-    protocol::{request::Request, response::Response, result::Result},
+    logic::{
+        amove::Move,
+        analysis::AnalysisLimit,
+        board::{Board, BoardFrontend},
+    },
+    protocol::{
+        connection::Connection,
+        handshake::{negotiate, Capability, PROTOCOL_VERSION},
+        request::{AnalysisTarget, Request, RequestEnvelope, RequestId},
+        response::{Response, ResponseEnvelope},
+        result::Result,
+        stream::authenticate_bot,
+    },
     state::{
-        client::{Client, Clients},
-        game::{Game, Games},
-        game_configuration::GameConfiguration,
-        game_meta::GameMeta,
+        challenge::{expire_later, Challenge, ChallengeUUID},
+        client::{send_to, Client, ClientSender, Clients},
+        game::{PlayerAction, Role},
+        game_configuration::{GameConfiguration, PlayerType, TimeControl, TimeControlCategory},
+        game_handle::{find_game, launch_game, launch_seated_game, GameHandle, Games},
+        game_meta::GameState,
         game_uuid::GameUUID,
+        lobby::{GameSummary, Lobby, LobbyFilter, LobbyQuery},
+        matchmaking::{match_waiting, MatchRules, Matchmaking},
+        server_state::ServerState,
+        tournament::{
+            advance, start_round, Tournament, TournamentConfiguration, TournamentStatus,
+            TournamentUUID,
+        },
         user_uuid::UserUUID,
     },
 };
 use chrono::{DateTime, Duration, Utc};
-use futures::{future::join_all, FutureExt, StreamExt};
+use futures::{
+    future::{join_all, ready},
+    FutureExt, Stream, StreamExt,
+};
 use log::{error, info, warn};
 use serde_json::from_str;
-use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::pin::pin;
+use tokio::{sync::mpsc, time::Instant};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use uuid::Uuid;
 use warp::{
@@ -23,150 +46,649 @@ use warp::{
     reply::Reply,
 };
 
+fn game_not_found(game_uuid: &GameUUID) -> Response {
+    warn!("Game uuid does not exist: {}", game_uuid);
+    Response::Error {
+        message: format!("Game {} does not exist", game_uuid),
+    }
+}
+
+fn game_error(message: &str) -> Response {
+    Response::Error {
+        message: message.to_owned(),
+    }
+}
+
+// The [request_id] of a message that is not a valid request, so that its error can be matched
+fn lenient_request_id(message: &str) -> Option<RequestId> {
+    let value: serde_json::Value = from_str(message).ok()?;
+    Some(value.get("request_id")?.as_str()?.to_owned())
+}
+
+// Clients that send bare requests and did not ask for [Acks] do not know about [Ack] and [Error]
+async fn wants_acks(
+    request_id: &Option<RequestId>,
+    client_uuid: &UserUUID,
+    clients: &Clients,
+) -> bool {
+    request_id.is_some()
+        || clients
+            .lock()
+            .await
+            .get(client_uuid)
+            .is_some_and(|client| client.capabilities.contains(&Capability::Acks))
+}
+
+async fn hello(
+    protocol_version: u32,
+    capabilities: Vec<Capability>,
+    client_uuid: &UserUUID,
+    clients: &Clients,
+) -> Response {
+    match negotiate(protocol_version, &capabilities) {
+        Ok(capabilities) => {
+            if let Some(client) = clients.lock().await.get_mut(client_uuid) {
+                client.capabilities.clone_from(&capabilities);
+            }
+            Response::Hello {
+                protocol_version: PROTOCOL_VERSION,
+                capabilities,
+            }
+        }
+        Err(message) => {
+            warn!("[hello]: {}: {}", client_uuid, message);
+            Response::Error { message }
+        }
+    }
+}
+
 async fn change_profile_info(
     new_user_name: String,
     new_user_avatar: String,
-    client_uuid: UserUUID,
+    client_uuid: &UserUUID,
     clients: &Clients,
-) {
-    let mut clients_guard = clients.lock().await;
-
-    match clients_guard.get_mut(&client_uuid) {
+) -> Response {
+    match clients.lock().await.get_mut(client_uuid) {
         Some(client) => {
             client.user_name.clone_from(&new_user_name);
             client.user_avatar_uuid.clone_from(&new_user_avatar);
         }
         None => {
             warn!("Client UUID does not exist, ignore"); // TODO
-            return;
+            return Response::Error {
+                message: "Client does not exist".to_owned(),
+            };
         }
     }
 
-    let res = Response::ChangeProfileInfo {
+    Response::ChangeProfileInfo {
         status: 200,
         user_name: new_user_name,
         user_avatar: new_user_avatar,
-    };
+    }
+}
 
-    clients_guard
-        .get(&client_uuid)
-        .expect("Client UUID does not exist")
-        .sender
-        .iter()
-        .for_each(|sender| {
-            let _ = sender.send(Ok(Message::text(serde_json::to_string(&res).unwrap())));
-        });
+async fn get_profile(
+    user_uuid: Option<UserUUID>,
+    client_uuid: &UserUUID,
+    state: &ServerState,
+) -> Response {
+    let user_uuid = user_uuid.unwrap_or_else(|| client_uuid.clone());
+    let user_name = state
+        .clients
+        .lock()
+        .await
+        .get(&user_uuid)
+        .map(|client| client.user_name.clone());
+    let ratings = state.ratings.read().await.profile(&user_uuid);
+    Response::Profile {
+        user_uuid,
+        user_name,
+        ratings,
+    }
+}
+
+const BOTS_ARE_UNRATED: &str = "Bots may not play rated games";
+const TIME_CONTROL_OUT_OF_RANGE: &str = "The time control is out of range";
+
+fn is_out_of_range(time_control: Option<&TimeControl>) -> bool {
+    time_control.is_some_and(|time_control| !time_control.is_in_range())
+}
+
+async fn is_bot(client_uuid: &UserUUID, state: &ServerState) -> bool {
+    state.bots.read().await.is_bot(client_uuid)
 }
 
 async fn create_game(
-    game_configuration: GameConfiguration,
-    client_uuid: UserUUID,
-    clients: &Clients,
-    games: &Games,
-) {
+    mut game_configuration: GameConfiguration,
+    client_uuid: &UserUUID,
+    state: &ServerState,
+) -> Response {
+    if game_configuration.rated && is_bot(client_uuid, state).await {
+        return game_error(BOTS_ARE_UNRATED);
+    }
+    if is_out_of_range(game_configuration.time_control.as_ref()) {
+        return game_error(TIME_CONTROL_OUT_OF_RANGE);
+    }
+    game_configuration.private = false; // Only accepted challenges create private games
     let game_uuid: String = Uuid::new_v4().simple().to_string();
-    let game = Game::new(
+    launch_game(
         game_uuid.clone(),
         client_uuid.clone(),
         game_configuration,
-        Arc::clone(clients),
-    );
-
-    games.lock().await.insert(game_uuid.clone(), game);
-    let res = Response::CreateGame {
+        state,
+    )
+    .await;
+    Response::CreateGame {
         status: 200,
         game_uuid,
-    };
-
-    clients
-        .lock()
-        .await
-        .get(&client_uuid)
-        .expect("Client UUID does not exist")
-        .sender
-        .iter()
-        .for_each(|sender| {
-            let _ = sender.send(Ok(Message::text(serde_json::to_string(&res).unwrap())));
-        });
+    }
 }
 
-async fn get_available_games(client_uuid: &UserUUID, clients: &Clients, games: &Games) {
+async fn get_available_games(query: LobbyQuery, games: &Games) -> Response {
     fn is_two_weeks_ago_or_later(date: DateTime<Utc>) -> bool {
         Utc::now() <= date + Duration::weeks(2)
     }
 
-    let available_games: Vec<(GameUUID, GameMeta, GameConfiguration)> = {
-        let games_guard = games.lock().await;
-        let futures = games_guard.iter().map(|(game_uuid, game)| {
-            let game_uuid = game_uuid.clone();
-            let game_meta_future = game.get_meta();
-            let game_description = game.get_description().clone();
+    // Snapshot the index first, so that it is not locked while waiting for the games
+    let games_snapshot: Vec<(GameUUID, GameHandle)> = games
+        .read()
+        .await
+        .iter()
+        .map(|(game_uuid, game)| (game_uuid.clone(), game.clone()))
+        .collect();
 
-            async move {
-                let game_meta = game_meta_future.await;
-                (game_uuid, game_meta, game_description)
-            }
-        });
-        join_all(futures).await
-    }
+    let available_games: Vec<GameSummary> = join_all(
+        games_snapshot
+            .into_iter()
+            .map(|(_, game)| async move { game.summary().await.ok() }),
+    )
+    .await
     .into_iter()
-    .filter(|game| is_two_weeks_ago_or_later(game.1.created_at))
+    .flatten()
+    .filter(|game| !game.2.private && is_two_weeks_ago_or_later(game.1.created_at))
     .collect();
 
-    let res = Response::AvailableGames { available_games };
+    let (available_games, total) = query.apply(available_games);
+    Response::AvailableGames {
+        available_games,
+        total,
+    }
+}
+
+// Subscribe first, then fetch [GetAvailableGames], so that no change is missed in between
+fn subscribe_lobby(filter: LobbyFilter, lobby: &Lobby, connection: &mut Connection) -> Response {
+    connection.subscribe_lobby(lobby.subscribe(), filter);
+    Response::Ack {}
+}
 
-    clients
+async fn find_match(
+    time_control: Option<TimeControl>,
+    rules: MatchRules,
+    client_uuid: &UserUUID,
+    state: &ServerState,
+) -> Response {
+    if rules.rated && is_bot(client_uuid, state).await {
+        return game_error(BOTS_ARE_UNRATED);
+    }
+    if is_out_of_range(time_control.as_ref()) {
+        return game_error(TIME_CONTROL_OUT_OF_RANGE);
+    }
+    // Rated searches are matched by rating, once the client has played in the pool
+    let pool = time_control
+        .as_ref()
+        .map_or(TimeControlCategory::Unlimited, TimeControl::category);
+    let rating = Some(state.ratings.read().await.get(client_uuid, pool))
+        .filter(|rating| rules.rated && rating.games > 0)
+        .map(|rating| rating.rating);
+    state.matchmaking.lock().await.seek(
+        client_uuid.clone(),
+        time_control,
+        rules,
+        rating,
+        Instant::now(),
+    );
+    match_waiting(state).await;
+    Response::Ack {}
+}
+
+// The challenge is only sent if the target is online to see it
+async fn challenge(
+    target_user_uuid: UserUUID,
+    mut game_configuration: GameConfiguration,
+    client_uuid: &UserUUID,
+    state: &ServerState,
+) -> Response {
+    if game_configuration.opponent == PlayerType::Computer {
+        return game_error("Only people and bots can be challenged");
+    }
+    if is_out_of_range(game_configuration.time_control.as_ref()) {
+        return game_error(TIME_CONTROL_OUT_OF_RANGE);
+    }
+    if game_configuration.rated
+        && (is_bot(client_uuid, state).await || is_bot(&target_user_uuid, state).await)
+    {
+        return game_error(BOTS_ARE_UNRATED);
+    }
+    let (challenger_name, target_is_online) = {
+        let clients = state.clients.lock().await;
+        (
+            clients
+                .get(client_uuid)
+                .map(|client| client.user_name.clone()),
+            clients
+                .get(&target_user_uuid)
+                .is_some_and(|client| client.sender.is_some()),
+        )
+    };
+    if !target_is_online {
+        return game_error("The user is not online");
+    }
+
+    game_configuration.private = true;
+    let ttl = state.config.challenge_ttl;
+    let expires_at = Duration::from_std(ttl)
+        .ok()
+        .and_then(|ttl| Utc::now().checked_add_signed(ttl));
+    let Some(expires_at) = expires_at else {
+        error!(
+            "[challenge]: the challenge lifetime {:?} is out of range",
+            ttl
+        );
+        return game_error("Challenges are not available");
+    };
+    let challenge = Challenge {
+        challenge_uuid: Uuid::new_v4().simple().to_string(),
+        challenger_uuid: client_uuid.clone(),
+        challenger_name: challenger_name.unwrap_or_default(),
+        target_user_uuid,
+        game_configuration,
+        expires_at,
+    };
+    if let Err(e) = state.challenges.lock().await.issue(challenge.clone()) {
+        return game_error(e);
+    }
+    expire_later(state, challenge.challenge_uuid.clone(), ttl);
+    let target = challenge.target_user_uuid.clone();
+    let res = Response::Challenge { challenge };
+    send_to(&state.clients, &[target], &res).await;
+    res
+}
+
+// Seats the challenger first, so that the side they asked for is respected. Both are seated before
+// the game is added, so a game that cannot start leaves nothing behind.
+async fn accept_challenge(
+    challenge_uuid: ChallengeUUID,
+    client_uuid: &UserUUID,
+    state: &ServerState,
+) -> Response {
+    let accepted = state
+        .challenges
         .lock()
         .await
-        .get(client_uuid)
-        .expect("Client UUID does not exist")
-        .sender
-        .iter()
-        .for_each(|sender| {
-            let _ = sender.send(Ok(Message::text(serde_json::to_string(&res).unwrap())));
-        });
+        .accept(&challenge_uuid, client_uuid, Utc::now());
+    let challenge = match accepted {
+        Ok(challenge) => challenge,
+        Err(e) => return game_error(e),
+    };
+    let game_uuid = Uuid::new_v4().simple().to_string();
+    let seated = launch_seated_game(
+        game_uuid.clone(),
+        (challenge.challenger_uuid.clone(), client_uuid.clone()),
+        challenge.game_configuration,
+        state,
+    )
+    .await;
+    if let Err(e) = seated {
+        return game_error(e);
+    }
+
+    let res = Response::ChallengeAccepted {
+        challenge_uuid,
+        game_uuid,
+    };
+    send_to(&state.clients, &[challenge.challenger_uuid], &res).await;
+    res
+}
+
+async fn cancel_match(client_uuid: &UserUUID, matchmaking: &Matchmaking) -> Response {
+    if matchmaking.lock().await.cancel(client_uuid) {
+        Response::Ack {}
+    } else {
+        game_error("You are not looking for a match")
+    }
+}
+
+async fn create_tournament(
+    configuration: TournamentConfiguration,
+    client_uuid: &UserUUID,
+    state: &ServerState,
+) -> Response {
+    if configuration.name.trim().is_empty() {
+        return game_error("The tournament needs a name");
+    }
+    if is_out_of_range(configuration.time_control.as_ref()) {
+        return game_error(TIME_CONTROL_OUT_OF_RANGE);
+    }
+    let tournament_uuid = Uuid::new_v4().simple().to_string();
+    let tournament = Tournament::new(tournament_uuid.clone(), client_uuid.clone(), configuration);
+    state
+        .tournaments
+        .write()
+        .await
+        .insert(tournament_uuid.clone(), tournament);
+    Response::TournamentCreated { tournament_uuid }
+}
+
+async fn get_tournaments(state: &ServerState) -> Response {
+    let tournaments = state
+        .tournaments
+        .read()
+        .await
+        .values()
+        .filter(|tournament| tournament.status != TournamentStatus::Completed)
+        .cloned()
+        .collect();
+    Response::Tournaments { tournaments }
+}
+
+async fn get_tournament(tournament_uuid: &TournamentUUID, state: &ServerState) -> Response {
+    match state.tournaments.read().await.get(tournament_uuid) {
+        Some(tournament) => Response::Tournament {
+            tournament: tournament.clone(),
+            standings: tournament.standings(),
+        },
+        None => game_error("The tournament does not exist"),
+    }
 }
 
-async fn join_game(client_uuid: UserUUID, game_uuid: String, games: &Games) {
-    match games.lock().await.get_mut(&game_uuid) {
-        Some(game) => {
-            game.add_client(client_uuid).await;
+// Registration changes and starting the tournament share the lookup and the error handling
+async fn update_tournament(
+    tournament_uuid: &TournamentUUID,
+    state: &ServerState,
+    update: impl FnOnce(&mut Tournament) -> std::result::Result<(), &'static str>,
+) -> Response {
+    {
+        let mut tournaments = state.tournaments.write().await;
+        let Some(tournament) = tournaments.get_mut(tournament_uuid) else {
+            return game_error("The tournament does not exist");
+        };
+        if let Err(message) = update(tournament) {
+            return game_error(message);
         }
-        None => {
-            warn!("Game uuid does not exist: {}", game_uuid);
+    }
+    // Pairs the first round once started
+    if let Some(round_start) = advance(state, tournament_uuid).await {
+        start_round(state, round_start).await;
+    }
+    Response::Ack {}
+}
+
+async fn join_game(
+    game_uuid: GameUUID,
+    role: Option<Role>,
+    games: &Games,
+    connection: &mut Connection,
+) -> Response {
+    let Some(game) = find_game(games, &game_uuid).await else {
+        return game_not_found(&game_uuid);
+    };
+
+    let events = game.subscribe();
+    match game.join(connection.client_uuid.clone(), role).await {
+        Ok(_) => {
+            connection.subscribe(game_uuid, events);
+            Response::Ack {}
         }
+        Err(message) => game_error(message),
+    }
+}
+
+async fn leave_game(game_uuid: GameUUID, games: &Games, connection: &mut Connection) -> Response {
+    let Some(game) = find_game(games, &game_uuid).await else {
+        return game_not_found(&game_uuid);
     };
+
+    match game.leave(connection.client_uuid.clone()).await {
+        Ok(()) => {
+            connection.unsubscribe(&game_uuid);
+            Response::Ack {}
+        }
+        Err(message) => game_error(message),
+    }
 }
 
-async fn get_game_state(client_uuid: &UserUUID, game_uuid: &String, games: &Games) {
-    match games.lock().await.get_mut(game_uuid) {
-        Some(game) => game.emit_board(client_uuid).await,
-        None => {
-            warn!("Game uuid does not exist: {}", game_uuid);
+// Subscribes the client again to every unfinished game it takes part in; the games resend their state
+async fn reconnect(games: &Games, connection: &mut Connection) -> Response {
+    let games_snapshot: Vec<(GameUUID, GameHandle)> = games
+        .read()
+        .await
+        .iter()
+        .map(|(game_uuid, game)| (game_uuid.clone(), game.clone()))
+        .collect();
+
+    let mut game_uuids = vec![];
+    for (game_uuid, game) in games_snapshot {
+        let events = game.subscribe();
+        if let Ok(true) = game.reconnect(connection.client_uuid.clone()).await {
+            connection.subscribe(game_uuid.clone(), events);
+            game_uuids.push(game_uuid);
         }
+    }
+    Response::Reconnected { game_uuids }
+}
+
+async fn get_game_state(game_uuid: &GameUUID, games: &Games) -> Response {
+    match find_game(games, game_uuid).await {
+        Some(game) => game.game_state().await.unwrap_or_else(game_error),
+        None => game_not_found(game_uuid),
+    }
+}
+
+async fn make_move(
+    mv: Move,
+    game_uuid: &GameUUID,
+    client_uuid: &UserUUID,
+    games: &Games,
+) -> Response {
+    match find_game(games, game_uuid).await {
+        Some(game) => match game.make_move(client_uuid.clone(), mv).await {
+            Ok(()) => Response::Ack {},
+            Err(message) => {
+                warn!("Move {} rejected: {}", mv, message);
+                game_error(message)
+            }
+        },
+        None => game_not_found(game_uuid),
+    }
+}
+
+async fn preview_move(mv: Move, game_uuid: &GameUUID, games: &Games) -> Response {
+    match find_game(games, game_uuid).await {
+        Some(game) => game.preview_move(mv).await.unwrap_or_else(game_error),
+        None => game_not_found(game_uuid),
+    }
+}
+
+async fn send_chat(
+    text: String,
+    game_uuid: &GameUUID,
+    client_uuid: &UserUUID,
+    games: &Games,
+) -> Response {
+    match find_game(games, game_uuid).await {
+        Some(game) => match game.send_chat(client_uuid.clone(), text).await {
+            Ok(()) => Response::Ack {},
+            Err(message) => game_error(message),
+        },
+        None => game_not_found(game_uuid),
+    }
+}
+
+// The search runs on the analysis workers; the game counts the hint once it is found
+async fn request_hint(
+    game_uuid: GameUUID,
+    client_uuid: &UserUUID,
+    state: &ServerState,
+) -> Response {
+    let Some(game) = find_game(&state.games, &game_uuid).await else {
+        return game_not_found(&game_uuid);
     };
+    let board = match game.hint_position(client_uuid.clone()).await {
+        Ok(board) => board,
+        Err(message) => return game_error(message),
+    };
+    match state.analysis.hint(board).await {
+        Ok((mv, reason)) => {
+            game.count_hint(client_uuid.clone()).await;
+            Response::Hint {
+                game_uuid,
+                mv,
+                reason,
+            }
+        }
+        Err(message) => game_error(message),
+    }
 }
 
-async fn make_move(mv: Move, game_uuid: &String, client_uuid: &UserUUID, games: &Games) {
-    match games.lock().await.get_mut(game_uuid) {
-        Some(game) => {
-            let _ = game.make_move(client_uuid, mv).await; // TODO: handle the result
+async fn player_action(
+    action: PlayerAction,
+    game_uuid: &GameUUID,
+    client_uuid: &UserUUID,
+    games: &Games,
+) -> Response {
+    match find_game(games, game_uuid).await {
+        Some(game) => match game.act(client_uuid.clone(), action).await {
+            Ok(()) => Response::Ack {},
+            Err(message) => game_error(message),
+        },
+        None => game_not_found(game_uuid),
+    }
+}
+
+// A game is analyzed by uuid only once it is over, so that the analysis cannot help its players
+async fn completed_game_moves(
+    game_uuid: &GameUUID,
+    state: &ServerState,
+) -> std::result::Result<Vec<Move>, &'static str> {
+    let record = match find_game(&state.games, game_uuid).await {
+        Some(game) => game.record().await?,
+        None => state
+            .archive
+            .read()
+            .await
+            .records()
+            .find(|record| record.game_uuid == *game_uuid)
+            .cloned()
+            .ok_or("The game does not exist")?,
+    };
+    if record.game_meta.status != GameState::Completed {
+        return Err("Only completed games can be analyzed");
+    }
+    Ok(record.moves)
+}
+
+// Whether [board] is the current position of a game [client_uuid] is playing. Each game only
+// hands out its board to its own players, so nothing is replayed or copied for the others.
+async fn is_own_live_position(board: &Board, client_uuid: &UserUUID, state: &ServerState) -> bool {
+    let games: Vec<GameHandle> = state.games.read().await.values().cloned().collect();
+    for game in games {
+        if let Ok(Some(live)) = game.live_position(client_uuid.clone()).await {
+            if live == *board {
+                return true;
+            }
         }
-        None => {
-            warn!("Game uuid does not exists {}", game_uuid);
+    }
+    false
+}
+
+// Positions can be analyzed freely, except those of the caller's own live games, which could help
+// it in the middle of a game
+async fn analysis_position(
+    target: AnalysisTarget,
+    client_uuid: &UserUUID,
+    state: &ServerState,
+) -> std::result::Result<Board, &'static str> {
+    match target {
+        AnalysisTarget::Position { moves } => {
+            let board = Board::replay(&moves)?;
+            if is_own_live_position(&board, client_uuid, state).await {
+                return Err("Positions of your own live games cannot be analyzed");
+            }
+            Ok(board)
+        }
+        AnalysisTarget::Game {
+            game_uuid,
+            move_number,
+        } => {
+            let moves = completed_game_moves(&game_uuid, state).await?;
+            let moves = moves
+                .get(..move_number)
+                .ok_or("The game does not have that many moves")?;
+            Board::replay(moves)
         }
+    }
+}
+
+async fn analyze_position(
+    target: AnalysisTarget,
+    limit: AnalysisLimit,
+    lines: Option<usize>,
+    client_uuid: &UserUUID,
+    state: &ServerState,
+) -> Response {
+    let board = match analysis_position(target, client_uuid, state).await {
+        Ok(board) => board,
+        Err(message) => return game_error(message),
     };
+    let lines = lines.unwrap_or(3);
+    match state.analysis.analyze(board.clone(), limit, lines).await {
+        Ok(analysis) => Response::Analysis {
+            position: BoardFrontend::new(board),
+            analysis,
+        },
+        Err(message) => game_error(message),
+    }
+}
+
+// Completed games are annotated as soon as they end, so this is usually a lookup
+async fn get_game_analysis(game_uuid: GameUUID, state: &ServerState) -> Response {
+    let annotation = match state.analysis.annotation(&game_uuid).await {
+        Some(annotation) => Ok(annotation),
+        None => match completed_game_moves(&game_uuid, state).await {
+            Ok(moves) => state.analysis.annotate(game_uuid.clone(), moves).await,
+            Err(message) => Err(message),
+        },
+    };
+    match annotation {
+        Ok(annotation) => Response::GameAnalysis {
+            game_uuid,
+            annotation: (*annotation).clone(),
+        },
+        Err(message) => game_error(message),
+    }
 }
 
-// TODO: use proper type for [client_uuid]
-async fn process_client_msg(client_uuid: UserUUID, msg: Message, clients: &Clients, games: &Games) {
-    // Parse the message string into a `Request` enum.
-    let req: Request = match msg.to_str() {
+async fn process_client_msg(connection: &mut Connection, msg: Message, state: &ServerState) {
+    let client_uuid = connection.client_uuid.clone();
+    let (clients, games) = (&state.clients, &state.games);
+    // Parse the message string into a `RequestEnvelope`; bare `Request`s parse as well.
+    let RequestEnvelope {
+        request_id,
+        request: req,
+    } = match msg.to_str() {
         Ok(message) => match from_str(message) {
             Ok(req) => req,
             Err(e) => {
                 warn!("error while parsing message to topics request: {}", e);
                 warn!("{:#?}", msg);
+                let request_id = lenient_request_id(message);
+                if wants_acks(&request_id, &client_uuid, clients).await {
+                    connection.send(&ResponseEnvelope {
+                        request_id,
+                        response: game_error(&format!("The request is invalid: {}", e)),
+                    });
+                }
                 return;
             }
         },
@@ -176,39 +698,245 @@ async fn process_client_msg(client_uuid: UserUUID, msg: Message, clients: &Clien
             return;
         }
     };
-    info!("[process_client_msg]: {:#?}", req);
+    info!("[process_client_msg]: {:?} {:#?}", request_id, req);
 
-    match req {
+    let is_hello = matches!(req, Request::Hello { .. });
+    let res = match req {
+        Request::Hello {
+            protocol_version,
+            capabilities,
+        } => hello(protocol_version, capabilities, &client_uuid, clients).await,
         Request::ChangeProfileInfo {
             new_user_name,
             new_user_avatar,
-        } => change_profile_info(new_user_name, new_user_avatar, client_uuid, clients).await,
+        } => change_profile_info(new_user_name, new_user_avatar, &client_uuid, clients).await,
+        Request::GetProfile { user_uuid } => get_profile(user_uuid, &client_uuid, state).await,
         Request::CreateGame { game_configuration } => {
-            create_game(game_configuration, client_uuid, clients, games).await;
+            create_game(game_configuration, &client_uuid, state).await
+        }
+        Request::GetAvailableGames { query } => get_available_games(query, games).await,
+        Request::SubscribeLobby { filter } => subscribe_lobby(filter, &state.lobby, connection),
+        Request::UnsubscribeLobby {} => {
+            connection.unsubscribe_lobby();
+            Response::Ack {}
+        }
+        Request::FindMatch {
+            time_control,
+            rules,
+        } => find_match(time_control, rules, &client_uuid, state).await,
+        Request::CancelMatch {} => cancel_match(&client_uuid, &state.matchmaking).await,
+        Request::Challenge {
+            target_user_uuid,
+            game_configuration,
+        } => challenge(target_user_uuid, game_configuration, &client_uuid, state).await,
+        Request::AcceptChallenge { challenge_uuid } => {
+            accept_challenge(challenge_uuid, &client_uuid, state).await
+        }
+        Request::DeclineChallenge { challenge_uuid } => {
+            let declined = state
+                .challenges
+                .lock()
+                .await
+                .decline(&challenge_uuid, &client_uuid);
+            match declined {
+                Ok(challenge) => {
+                    let res = Response::ChallengeDeclined { challenge_uuid };
+                    send_to(clients, &[challenge.challenger_uuid], &res).await;
+                    Response::Ack {}
+                }
+                Err(e) => game_error(e),
+            }
+        }
+        Request::CancelChallenge { challenge_uuid } => {
+            let canceled = state
+                .challenges
+                .lock()
+                .await
+                .cancel(&challenge_uuid, &client_uuid);
+            match canceled {
+                Ok(challenge) => {
+                    let res = Response::ChallengeCanceled { challenge_uuid };
+                    send_to(clients, &[challenge.target_user_uuid], &res).await;
+                    Response::Ack {}
+                }
+                Err(e) => game_error(e),
+            }
+        }
+        Request::GetChallenges {} => Response::Challenges {
+            challenges: state.challenges.lock().await.involving(&client_uuid),
+        },
+        Request::CreateTournament { configuration } => {
+            create_tournament(configuration, &client_uuid, state).await
         }
-        Request::GetAvailableGames {} => get_available_games(&client_uuid, clients, games).await,
-        Request::JoinGame { game_uuid } => {
-            join_game(client_uuid, game_uuid.clone(), games).await;
-            // TODO: also update participants when someone leaves
+        Request::GetTournaments {} => get_tournaments(state).await,
+        Request::GetTournament { tournament_uuid } => get_tournament(&tournament_uuid, state).await,
+        Request::JoinTournament { tournament_uuid } => {
+            let is_bot = is_bot(&client_uuid, state).await;
+            update_tournament(&tournament_uuid, state, |tournament| {
+                if is_bot && tournament.configuration.rated {
+                    return Err(BOTS_ARE_UNRATED);
+                }
+                tournament.register(client_uuid.clone())
+            })
+            .await
         }
-        Request::GetGameState { game_uuid } => {
-            get_game_state(&client_uuid, &game_uuid, games).await
+        Request::LeaveTournament { tournament_uuid } => {
+            update_tournament(&tournament_uuid, state, |tournament| {
+                tournament.withdraw(&client_uuid)
+            })
+            .await
         }
-        Request::MakeMove { game_uuid, mv } => {
-            make_move(mv, &game_uuid, &client_uuid, games).await;
+        Request::StartTournament { tournament_uuid } => {
+            update_tournament(&tournament_uuid, state, |tournament| {
+                tournament.start(&client_uuid)
+            })
+            .await
         }
+        Request::Reconnect {} => reconnect(games, connection).await,
+        Request::JoinGame { game_uuid, role } => {
+            join_game(game_uuid, role, games, connection).await
+        }
+        Request::LeaveGame { game_uuid } => leave_game(game_uuid, games, connection).await,
+        Request::GetGameState { game_uuid } => get_game_state(&game_uuid, games).await,
+        Request::MakeMove { game_uuid, mv } => make_move(mv, &game_uuid, &client_uuid, games).await,
+        Request::PreviewMove { game_uuid, mv } => preview_move(mv, &game_uuid, games).await,
+        Request::Resign { game_uuid } => {
+            player_action(PlayerAction::Resign, &game_uuid, &client_uuid, games).await
+        }
+        Request::OfferDraw { game_uuid } => {
+            player_action(PlayerAction::OfferDraw, &game_uuid, &client_uuid, games).await
+        }
+        Request::AcceptDraw { game_uuid } => {
+            player_action(PlayerAction::AcceptDraw, &game_uuid, &client_uuid, games).await
+        }
+        Request::DeclineDraw { game_uuid } => {
+            player_action(PlayerAction::DeclineDraw, &game_uuid, &client_uuid, games).await
+        }
+        Request::Abort { game_uuid } => {
+            player_action(PlayerAction::Abort, &game_uuid, &client_uuid, games).await
+        }
+        Request::RequestTakeback { game_uuid } => {
+            player_action(
+                PlayerAction::RequestTakeback,
+                &game_uuid,
+                &client_uuid,
+                games,
+            )
+            .await
+        }
+        Request::AcceptTakeback { game_uuid } => {
+            player_action(
+                PlayerAction::AcceptTakeback,
+                &game_uuid,
+                &client_uuid,
+                games,
+            )
+            .await
+        }
+        Request::DeclineTakeback { game_uuid } => {
+            player_action(
+                PlayerAction::DeclineTakeback,
+                &game_uuid,
+                &client_uuid,
+                games,
+            )
+            .await
+        }
+        Request::SendChat { game_uuid, text } => {
+            send_chat(text, &game_uuid, &client_uuid, games).await
+        }
+        Request::RequestHint { game_uuid } => request_hint(game_uuid, &client_uuid, state).await,
+        Request::AnalyzePosition {
+            position,
+            limit,
+            lines,
+        } => analyze_position(position, limit, lines, &client_uuid, state).await,
+        Request::GetGameAnalysis { game_uuid } => get_game_analysis(game_uuid, state).await,
+    };
+
+    let wants_acks = is_hello || wants_acks(&request_id, &client_uuid, clients).await;
+    if !wants_acks && matches!(res, Response::Ack {} | Response::Error { .. }) {
+        return;
+    }
+    let handshake_failed = is_hello && matches!(res, Response::Error { .. });
+    let res = ResponseEnvelope {
+        request_id,
+        response: res,
     };
+    connection.send(&res);
+
+    if handshake_failed {
+        connection.close();
+    }
 }
 
-async fn client_connection(
-    ws: WebSocket,
+// Serves a client's requests until [inbound] ends or nobody receives its responses anymore.
+// The requests come from a WebSocket, or from the posts of a bot listening to its HTTP stream.
+pub(crate) async fn client_connection(
     client_uuid: UserUUID,
-    clients: Clients,
-    games: Games,
+    state: ServerState,
     mut client: Client,
+    client_sender: ClientSender,
+    inbound: impl Stream<Item = Message>,
 ) {
-    info!("[client_connection]: {}", client_uuid);
-    let (client_ws_sender, mut client_ws_rcv) = ws.split();
+    let mut inbound = pin!(inbound);
+    client.sender = Some(client_sender.clone());
+    state
+        .clients
+        .lock()
+        .await
+        .insert(client_uuid.clone(), client);
+    let mut connection = Connection::new(client_uuid.clone(), client_sender.clone());
+
+    info!("[client_connection]: Client {} connected", client_uuid);
+
+    loop {
+        let msg = tokio::select! {
+            msg = inbound.next() => msg,
+            _ = client_sender.closed() => None,
+        };
+        let Some(msg) = msg else {
+            break;
+        };
+        process_client_msg(&mut connection, msg, &state).await;
+    }
+
+    // A client that reconnected before this connection closed has already replaced it; its
+    // registration, searches and seats belong to the new connection now
+    let is_current = {
+        let mut clients = state.clients.lock().await;
+        let is_current = clients.get(&client_uuid).is_some_and(|client| {
+            client
+                .sender
+                .as_ref()
+                .is_some_and(|sender| sender.same_channel(&client_sender))
+        });
+        if is_current {
+            clients.remove(&client_uuid);
+        }
+        is_current
+    };
+    if !is_current {
+        info!("[client_connection]: Client {} replaced", client_uuid);
+        return;
+    }
+    state.matchmaking.lock().await.disconnect(&client_uuid);
+    for game_uuid in connection.subscribed_games() {
+        if let Some(game) = find_game(&state.games, game_uuid).await {
+            game.disconnect(client_uuid.clone()).await;
+        }
+    }
+    info!("[client_connection]: Client {} disconnected", client_uuid);
+}
+
+async fn websocket_connection(
+    ws: WebSocket,
+    client_uuid: UserUUID,
+    state: ServerState,
+    client: Client,
+) {
+    info!("[websocket_connection]: {}", client_uuid);
+    let (client_ws_sender, client_ws_rcv) = ws.split();
     let (client_sender, client_rcv) = mpsc::unbounded_channel();
 
     let client_rcv = UnboundedReceiverStream::new(client_rcv);
@@ -218,42 +946,234 @@ async fn client_connection(
         }
     }));
 
-    client.sender = Some(client_sender);
-    clients.lock().await.insert(client_uuid.clone(), client);
-
-    info!("[client_connection]: Client {} connected", client_uuid);
-
-    while let Some(result) = client_ws_rcv.next().await {
-        let msg = match result {
-            Ok(msg) => msg,
+    let receiving_uuid = client_uuid.clone();
+    let inbound = client_ws_rcv.scan((), move |_, result| {
+        ready(match result {
+            Ok(msg) => Some(msg),
             Err(e) => {
                 error!(
                     "error receiving ws message for id: {}): {}",
-                    client_uuid.clone(),
-                    e
+                    receiving_uuid, e
                 );
-                break;
+                None
             }
-        };
-        process_client_msg(client_uuid.clone(), msg, &clients, &games).await;
-    }
-
-    clients.lock().await.remove(&client_uuid);
-    info!("[client_connection]: Client {} disconnected", client_uuid);
+        })
+    });
+    client_connection(client_uuid, state, client, client_sender, inbound).await;
 }
 
+// Bots have to show their token; humans are known from their registration
 pub async fn ws_handler(
     ws: warp::ws::Ws,
     client_uuid: UserUUID,
-    clients: Clients,
-    games: Games,
-) -> Result<impl Reply> {
+    authorization: Option<String>,
+    state: ServerState,
+) -> Result<warp::reply::Response> {
     info!("[ws_handler]: {}", client_uuid);
-    let client = clients.lock().await.get(&client_uuid).cloned();
+    let client = if is_bot(&client_uuid, &state).await {
+        match authenticate_bot(&client_uuid, authorization.as_deref(), &state).await {
+            Ok(client) => Some(client),
+            Err(status) => return Ok(status.into_response()),
+        }
+    } else {
+        state.clients.lock().await.get(&client_uuid).cloned()
+    };
     match client {
-        Some(client) => Ok(ws.on_upgrade(move |socket| {
-            client_connection(socket, client_uuid, clients, games, client)
-        })),
+        Some(client) => Ok(ws
+            .on_upgrade(move |socket| websocket_connection(socket, client_uuid, state, client))
+            .into_response()),
         None => Err(warp::reject::not_found()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{
+        game_configuration::{ColorPreference, PlayerType},
+        server_config::ServerConfig,
+    };
+    use std::time::Duration;
+
+    fn new_user() -> UserUUID {
+        UserUUID::new(Uuid::new_v4().to_string()).unwrap()
+    }
+
+    fn client(user_uuid: &UserUUID) -> Client {
+        Client {
+            user_name: "Tester".to_owned(),
+            user_uuid: user_uuid.clone(),
+            user_avatar_uuid: String::new(),
+            capabilities: vec![],
+            sender: None,
+        }
+    }
+
+    fn configuration(rated: bool) -> GameConfiguration {
+        GameConfiguration {
+            creator_name: "Tester".to_owned(),
+            opponent: PlayerType::Human,
+            side_selection: ColorPreference::AlwaysWhite,
+            time_control: None,
+            rated,
+            private: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn replaced_connection_leaves_the_new_one_registered() {
+        let state = ServerState::new(ServerConfig::default());
+        let client_uuid = new_user();
+        let client = client(&client_uuid);
+        let connect = |client: Client| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            let (requests, inbound) = mpsc::unbounded_channel::<Message>();
+            let task = tokio::spawn(client_connection(
+                client_uuid.clone(),
+                state.clone(),
+                client,
+                sender.clone(),
+                UnboundedReceiverStream::new(inbound),
+            ));
+            (task, sender, receiver, requests)
+        };
+
+        // The page is refreshed: the new socket registers before the old one closes
+        let (old_task, _, _old_receiver, old_requests) = connect(client.clone());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let (_new_task, new_sender, _new_receiver, _new_requests) = connect(client);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(old_requests);
+        old_task.await.unwrap();
+
+        let clients = state.clients.lock().await;
+        let registered = clients[&client_uuid].sender.as_ref().unwrap();
+        assert!(registered.same_channel(&new_sender));
+    }
+
+    #[tokio::test]
+    async fn invalid_requests_are_answered_with_their_request_id() {
+        let state = ServerState::new(ServerConfig::default());
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut connection = Connection::new(new_user(), sender);
+        process_client_msg(
+            &mut connection,
+            Message::text(r#"{"request_id": "7", "NoSuchRequest": {}}"#),
+            &state,
+        )
+        .await;
+        let msg = receiver.try_recv().unwrap().unwrap();
+        let res: ResponseEnvelope = from_str(msg.to_str().unwrap()).unwrap();
+        assert_eq!(res.request_id.as_deref(), Some("7"));
+        assert!(matches!(res.response, Response::Error { .. }));
+
+        // Without a request_id, a client that did not ask for Acks gets nothing back
+        process_client_msg(
+            &mut connection,
+            Message::text(r#"{"NoSuchRequest": {}}"#),
+            &state,
+        )
+        .await;
+        process_client_msg(&mut connection, Message::text("not json"), &state).await;
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn own_live_positions_are_not_analyzed() {
+        let state = ServerState::new(ServerConfig::default());
+        let (white, black) = (new_user(), new_user());
+        let game_uuid = Uuid::new_v4().simple().to_string();
+        launch_seated_game(
+            game_uuid,
+            (white.clone(), black),
+            configuration(false),
+            &state,
+        )
+        .await
+        .unwrap();
+
+        let position = || AnalysisTarget::Position { moves: vec![] };
+        assert!(analysis_position(position(), &white, &state).await.is_err());
+        assert!(analysis_position(position(), &new_user(), &state)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn challenges_outliving_the_calendar_are_refused() {
+        let state = ServerState::new(ServerConfig {
+            challenge_ttl: Duration::MAX,
+            ..ServerConfig::default()
+        });
+        let (challenger, target) = (new_user(), new_user());
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        state.clients.lock().await.insert(
+            target.clone(),
+            Client {
+                sender: Some(sender),
+                ..client(&target)
+            },
+        );
+
+        let res = challenge(target, configuration(false), &challenger, &state).await;
+        assert!(matches!(res, Response::Error { .. }));
+        assert!(state
+            .challenges
+            .lock()
+            .await
+            .involving(&challenger)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn unplayable_challenges_leave_no_game() {
+        let state = ServerState::new(ServerConfig::default());
+        let challenger = new_user();
+        let (bot, _) = state.bots.write().await.register("Bot".to_owned());
+        state.clients.lock().await.insert(bot.clone(), client(&bot));
+        let challenge = Challenge {
+            challenge_uuid: Uuid::new_v4().simple().to_string(),
+            challenger_uuid: challenger,
+            challenger_name: "Tester".to_owned(),
+            target_user_uuid: bot.clone(),
+            game_configuration: configuration(true),
+            expires_at: Utc::now() + chrono::Duration::minutes(5),
+        };
+        state
+            .challenges
+            .lock()
+            .await
+            .issue(challenge.clone())
+            .unwrap();
+
+        let res = accept_challenge(challenge.challenge_uuid, &bot, &state).await;
+        assert!(matches!(res, Response::Error { .. }));
+        assert!(state.games.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn out_of_range_time_controls_are_refused() {
+        let state = ServerState::new(ServerConfig::default());
+        let huge = TimeControl {
+            time: Duration::MAX,
+            increment: Duration::MAX,
+        };
+        assert_eq!(huge.category(), TimeControlCategory::Classical);
+
+        let res = find_match(
+            Some(huge.clone()),
+            MatchRules::default(),
+            &new_user(),
+            &state,
+        )
+        .await;
+        assert!(matches!(res, Response::Error { .. }));
+        let game_configuration = GameConfiguration {
+            time_control: Some(huge),
+            ..configuration(false)
+        };
+        let res = create_game(game_configuration, &new_user(), &state).await;
+        assert!(matches!(res, Response::Error { .. }));
+        assert!(state.games.read().await.is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+use super::{game_uuid::GameUUID, server_config::ServerConfig};
+use crate::logic::{
+    amove::Move,
+    analysis::{analyze, annotate_game, AnalysisLimit, GameAnnotation, PositionAnalysis},
+    board::Board,
+    evaluation::Evaluation,
+    hint::{hint, HintReason},
+};
+use log::{info, warn};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+use tokio::sync::{oneshot, RwLock};
+
+type Job = Box<dyn FnOnce(&Evaluation) + Send>;
+
+// The annotations of completed games, oldest first; the oldest ones are dropped once
+// [capacity] is reached
+#[derive(Debug)]
+struct Annotations {
+    games: HashMap<GameUUID, Arc<GameAnnotation>>,
+    order: VecDeque<GameUUID>,
+    capacity: usize,
+}
+
+impl Annotations {
+    fn insert(&mut self, game_uuid: GameUUID, annotation: Arc<GameAnnotation>) {
+        if self.games.insert(game_uuid.clone(), annotation).is_some() {
+            return;
+        }
+        self.order.push_back(game_uuid);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.games.remove(&oldest);
+            }
+        }
+    }
+}
+
+// Analyses run on threads of their own rather than on the blocking pool the games' searches use,
+// so that reviewing games cannot hold up the computer's moves. Requests beyond [queue] waiting
+// ones are turned away.
+#[derive(Debug, Clone)]
+pub struct AnalysisPool {
+    jobs: mpsc::SyncSender<Job>,
+    annotations: Arc<RwLock<Annotations>>,
+    annotation_depth: u32,
+}
+
+impl AnalysisPool {
+    pub fn new(config: &ServerConfig, evaluation: Evaluation) -> Self {
+        let workers = config.analysis_workers.max(1);
+        let (jobs, receiver) = mpsc::sync_channel::<Job>(config.analysis_queue);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for worker in 0..workers {
+            let receiver = Arc::clone(&receiver);
+            thread::Builder::new()
+                .name(format!("analysis-{}", worker))
+                .spawn(move || loop {
+                    // The lock is released before the job runs, so the other workers keep taking jobs
+                    let job = receiver.lock().unwrap().recv();
+                    let Ok(job) = job else {
+                        return;
+                    };
+                    job(&evaluation);
+                })
+                .expect("Could not start an analysis worker");
+        }
+        info!("[analysis_pool]: {} workers", workers);
+        AnalysisPool {
+            jobs,
+            annotations: Arc::new(RwLock::new(Annotations {
+                games: HashMap::new(),
+                order: VecDeque::new(),
+                capacity: config.archive_capacity,
+            })),
+            annotation_depth: config.annotation_depth,
+        }
+    }
+
+    fn submit<T: Send + 'static>(
+        &self,
+        work: impl FnOnce(&Evaluation) -> T + Send + 'static,
+    ) -> Result<oneshot::Receiver<T>, &'static str> {
+        let (reply, response) = oneshot::channel();
+        let job: Job = Box::new(move |evaluation| {
+            let _ = reply.send(work(evaluation));
+        });
+        self.jobs.try_send(job).map_err(|e| match e {
+            mpsc::TrySendError::Full(_) => "Too many analyses are running, try again later",
+            mpsc::TrySendError::Disconnected(_) => "The analysis workers have stopped",
+        })?;
+        Ok(response)
+    }
+
+    pub async fn analyze(
+        &self,
+        board: Board,
+        limit: AnalysisLimit,
+        lines: usize,
+    ) -> Result<PositionAnalysis, &'static str> {
+        let response = self.submit(move |evaluation| analyze(&board, evaluation, limit, lines))?;
+        response
+            .await
+            .map_err(|_| "The analysis workers have stopped")?
+    }
+
+    pub async fn hint(&self, board: Board) -> Result<(Move, HintReason), &'static str> {
+        let response = self.submit(move |evaluation| hint(&board, evaluation))?;
+        response
+            .await
+            .map_err(|_| "The analysis workers have stopped")?
+    }
+
+    pub async fn annotation(&self, game_uuid: &GameUUID) -> Option<Arc<GameAnnotation>> {
+        self.annotations.read().await.games.get(game_uuid).cloned()
+    }
+
+    // Annotates a completed game and keeps the result for later requests
+    pub async fn annotate(
+        &self,
+        game_uuid: GameUUID,
+        moves: Vec<Move>,
+    ) -> Result<Arc<GameAnnotation>, &'static str> {
+        if let Some(annotation) = self.annotation(&game_uuid).await {
+            return Ok(annotation);
+        }
+        let depth = self.annotation_depth;
+        let response = self.submit(move |evaluation| annotate_game(&moves, evaluation, depth))?;
+        let annotation = Arc::new(
+            response
+                .await
+                .map_err(|_| "The analysis workers have stopped")??,
+        );
+        self.annotations
+            .write()
+            .await
+            .insert(game_uuid, Arc::clone(&annotation));
+        Ok(annotation)
+    }
+
+    // Annotates a game in the background as soon as it is over, so that the players do not wait
+    // for it later. If the workers are busy, the game is annotated on request instead.
+    pub fn annotate_later(&self, game_uuid: GameUUID, moves: Vec<Move>) {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = pool.annotate(game_uuid.clone(), moves).await {
+                warn!("[analysis_pool]: could not annotate {}: {}", game_uuid, e);
+            }
+        });
+    }
+}
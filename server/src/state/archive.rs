@@ -0,0 +1,55 @@
+use super::{
+    chat::ChatEntry, game::Player, game_configuration::GameConfiguration, game_meta::GameMeta,
+    game_uuid::GameUUID, user_uuid::UserUUID,
+};
+use crate::logic::amove::Move;
+use std::{collections::VecDeque, sync::Arc};
+use tokio::sync::RwLock;
+
+// What is kept of a game once it is removed from [Games]
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    pub game_uuid: GameUUID,
+    pub game_meta: GameMeta,
+    pub game_configuration: GameConfiguration,
+    pub player_white: Option<(UserUUID, Player)>,
+    pub player_black: Option<(UserUUID, Player)>,
+    pub moves: Vec<Move>,
+    pub chat: Vec<ChatEntry>,
+}
+
+// Completed games, oldest first; the oldest ones are dropped once [capacity] is reached
+#[derive(Debug)]
+pub struct GameArchive {
+    records: VecDeque<GameRecord>,
+    capacity: usize,
+}
+pub type Archive = Arc<RwLock<GameArchive>>;
+
+impl GameArchive {
+    pub fn new(capacity: usize) -> Self {
+        GameArchive {
+            records: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn insert(&mut self, record: GameRecord) {
+        if self.records.len() >= self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &GameRecord> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
@@ -0,0 +1,182 @@
+use super::{
+    client::{Client, ClientSender},
+    user_uuid::UserUUID,
+};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{mpsc, RwLock};
+use uuid::Uuid;
+use warp::filters::ws::Message;
+
+#[derive(Debug, Clone)]
+pub struct BotAccount {
+    pub user_uuid: UserUUID,
+    pub user_name: String,
+    token: String, // Sent as `Authorization: Bearer <token>` on every connection
+}
+
+impl BotAccount {
+    // The client a connecting bot is registered as; bots have no avatar
+    pub fn client(&self) -> Client {
+        Client {
+            user_name: self.user_name.clone(),
+            user_uuid: self.user_uuid.clone(),
+            user_avatar_uuid: String::new(),
+            capabilities: vec![],
+            sender: None,
+        }
+    }
+}
+
+// Bot accounts, and the requests of bots connected through the HTTP stream rather than a
+// WebSocket. A bot has at most one open stream; its requests are posted separately and fed into
+// the stream's connection as if they had arrived on a WebSocket.
+#[derive(Debug, Default)]
+pub struct BotRegistry {
+    accounts: HashMap<UserUUID, BotAccount>,
+    streams: HashMap<UserUUID, (mpsc::UnboundedSender<Message>, ClientSender)>,
+}
+pub type Bots = Arc<RwLock<BotRegistry>>;
+
+// Compares every byte, so that the time taken does not tell how much of a token was right
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |difference, (x, y)| difference | (x ^ y))
+            == 0
+}
+
+// The token of an `Authorization: Bearer <token>` header
+pub fn bearer_token(authorization: Option<&str>) -> Option<&str> {
+    authorization?.strip_prefix("Bearer ").map(str::trim)
+}
+
+// Bot accounts are created by the server's operator, who shows the token configured as
+// [ServerConfig::bot_operator_token]. Without one, no accounts can be created.
+pub fn is_operator(
+    operator_token: Option<&str>,
+    authorization: Option<&str>,
+) -> Result<(), &'static str> {
+    let operator_token = operator_token.ok_or("Bot registration is disabled")?;
+    match bearer_token(authorization) {
+        Some(token) if same_token(token, operator_token) => Ok(()),
+        _ => Err("Invalid operator token"),
+    }
+}
+
+impl BotRegistry {
+    // Creates an account; the token is only handed out here
+    pub fn register(&mut self, user_name: String) -> (UserUUID, String) {
+        let user_uuid = UserUUID::new(Uuid::new_v4().to_string()).unwrap();
+        let token = Uuid::new_v4().simple().to_string();
+        self.accounts.insert(
+            user_uuid.clone(),
+            BotAccount {
+                user_uuid: user_uuid.clone(),
+                user_name,
+                token: token.clone(),
+            },
+        );
+        (user_uuid, token)
+    }
+
+    pub fn is_bot(&self, user_uuid: &UserUUID) -> bool {
+        self.accounts.contains_key(user_uuid)
+    }
+
+    pub fn authenticate(
+        &self,
+        user_uuid: &UserUUID,
+        token: Option<&str>,
+    ) -> Result<&BotAccount, &'static str> {
+        let account = self.accounts.get(user_uuid).ok_or("Unknown bot")?;
+        match token {
+            Some(token) if same_token(token, &account.token) => Ok(account),
+            _ => Err("Invalid bot token"),
+        }
+    }
+
+    // Fails if the bot already listens to a stream that is still open
+    pub fn open_stream(
+        &mut self,
+        user_uuid: UserUUID,
+        requests: mpsc::UnboundedSender<Message>,
+        sender: ClientSender,
+    ) -> Result<(), &'static str> {
+        if self
+            .streams
+            .get(&user_uuid)
+            .is_some_and(|(_, sender)| !sender.is_closed())
+        {
+            return Err("The bot already has an open stream");
+        }
+        self.streams.insert(user_uuid, (requests, sender));
+        Ok(())
+    }
+
+    // Leaves alone a stream the bot opened in the meantime
+    pub fn close_stream(&mut self, user_uuid: &UserUUID, sender: &ClientSender) {
+        if self
+            .streams
+            .get(user_uuid)
+            .is_some_and(|(_, open)| open.same_channel(sender))
+        {
+            self.streams.remove(user_uuid);
+        }
+    }
+
+    // Where the requests posted by the bot go
+    pub fn stream(&self, user_uuid: &UserUUID) -> Option<mpsc::UnboundedSender<Message>> {
+        self.streams
+            .get(user_uuid)
+            .filter(|(_, sender)| !sender.is_closed())
+            .map(|(requests, _)| requests.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_authenticate_their_bot_only() {
+        let mut bots = BotRegistry::default();
+        let (bot, token) = bots.register("bot".to_owned());
+        let (other, other_token) = bots.register("other".to_owned());
+        assert!(bots.is_bot(&bot));
+
+        assert_eq!(
+            bots.authenticate(&bot, Some(&token)).unwrap().user_name,
+            "bot"
+        );
+        assert!(bots.authenticate(&bot, Some(&other_token)).is_err());
+        assert!(bots.authenticate(&bot, None).is_err());
+        assert!(bots.authenticate(&other, Some(&token[1..])).is_err());
+        assert_eq!(
+            bearer_token(Some(&format!("Bearer {}", token))),
+            Some(token.as_str())
+        );
+        assert_eq!(bearer_token(Some(&token)), None);
+
+        // A second stream is turned away while the first one is open
+        let (requests, _) = mpsc::unbounded_channel();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        bots.open_stream(bot.clone(), requests.clone(), sender.clone())
+            .unwrap();
+        assert!(bots.stream(&bot).is_some());
+        assert!(bots
+            .open_stream(bot.clone(), requests.clone(), sender.clone())
+            .is_err());
+        drop(receiver);
+        assert!(bots.stream(&bot).is_none());
+        assert!(bots.open_stream(bot, requests, sender).is_ok());
+    }
+
+    #[test]
+    fn only_the_operator_registers_bots() {
+        assert!(is_operator(None, Some("Bearer secret")).is_err());
+        assert!(is_operator(Some("secret"), None).is_err());
+        assert!(is_operator(Some("secret"), Some("Bearer guess")).is_err());
+        assert!(is_operator(Some("secret"), Some("Bearer secret")).is_ok());
+    }
+}
@@ -0,0 +1,188 @@
+use super::{
+    client::send_to, game_configuration::GameConfiguration, server_state::ServerState,
+    user_uuid::UserUUID,
+};
+use crate::protocol::response::Response;
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+pub type ChallengeUUID = String;
+
+// An invitation to a private game, waiting for its target to answer
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone)]
+pub struct Challenge {
+    pub challenge_uuid: ChallengeUUID,
+    pub challenger_uuid: UserUUID,
+    pub challenger_name: String,
+    pub target_user_uuid: UserUUID,
+    pub game_configuration: GameConfiguration,
+    pub expires_at: DateTime<Utc>,
+}
+
+// The pending challenges. A challenge leaves the book when it is answered, canceled or expires,
+// so each of these happens at most once.
+#[derive(Debug, Default)]
+pub struct ChallengeBook {
+    challenges: HashMap<ChallengeUUID, Challenge>,
+}
+pub type Challenges = Arc<Mutex<ChallengeBook>>;
+
+impl ChallengeBook {
+    pub fn issue(&mut self, challenge: Challenge) -> Result<(), &'static str> {
+        if challenge.challenger_uuid == challenge.target_user_uuid {
+            return Err("You cannot challenge yourself");
+        }
+        if self.challenges.values().any(|pending| {
+            pending.challenger_uuid == challenge.challenger_uuid
+                && pending.target_user_uuid == challenge.target_user_uuid
+        }) {
+            return Err("You have already challenged this user");
+        }
+        self.challenges
+            .insert(challenge.challenge_uuid.clone(), challenge);
+        Ok(())
+    }
+
+    // Takes the challenge out of the book if [is_allowed] holds for it
+    fn answer(
+        &mut self,
+        challenge_uuid: &ChallengeUUID,
+        is_allowed: impl FnOnce(&Challenge) -> bool,
+    ) -> Result<Challenge, &'static str> {
+        let challenge = self
+            .challenges
+            .get(challenge_uuid)
+            .ok_or("The challenge does not exist")?;
+        if !is_allowed(challenge) {
+            return Err("The challenge is not yours to answer");
+        }
+        Ok(self.challenges.remove(challenge_uuid).unwrap())
+    }
+
+    pub fn accept(
+        &mut self,
+        challenge_uuid: &ChallengeUUID,
+        user_uuid: &UserUUID,
+        now: DateTime<Utc>,
+    ) -> Result<Challenge, &'static str> {
+        let challenge = self.answer(challenge_uuid, |challenge| {
+            &challenge.target_user_uuid == user_uuid
+        })?;
+        if challenge.expires_at <= now {
+            return Err("The challenge has expired");
+        }
+        Ok(challenge)
+    }
+
+    pub fn decline(
+        &mut self,
+        challenge_uuid: &ChallengeUUID,
+        user_uuid: &UserUUID,
+    ) -> Result<Challenge, &'static str> {
+        self.answer(challenge_uuid, |challenge| {
+            &challenge.target_user_uuid == user_uuid
+        })
+    }
+
+    pub fn cancel(
+        &mut self,
+        challenge_uuid: &ChallengeUUID,
+        user_uuid: &UserUUID,
+    ) -> Result<Challenge, &'static str> {
+        self.answer(challenge_uuid, |challenge| {
+            &challenge.challenger_uuid == user_uuid
+        })
+    }
+
+    // None if the challenge was answered in the meantime
+    pub fn expire(&mut self, challenge_uuid: &ChallengeUUID) -> Option<Challenge> {
+        self.challenges.remove(challenge_uuid)
+    }
+
+    // The challenges a user has sent or received
+    pub fn involving(&self, user_uuid: &UserUUID) -> Vec<Challenge> {
+        self.challenges
+            .values()
+            .filter(|challenge| {
+                &challenge.challenger_uuid == user_uuid || &challenge.target_user_uuid == user_uuid
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+// Drops the challenge once [ttl] has passed, unless it was answered before, and tells both users
+pub fn expire_later(state: &ServerState, challenge_uuid: ChallengeUUID, ttl: Duration) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(ttl).await;
+        let Some(challenge) = state.challenges.lock().await.expire(&challenge_uuid) else {
+            return;
+        };
+        let users = [challenge.challenger_uuid, challenge.target_user_uuid];
+        send_to(
+            &state.clients,
+            &users,
+            &Response::ChallengeExpired { challenge_uuid },
+        )
+        .await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::game_configuration::{ColorPreference, PlayerType};
+
+    fn new_user() -> UserUUID {
+        UserUUID::new(uuid::Uuid::new_v4().to_string()).unwrap()
+    }
+
+    fn challenge(challenger: &UserUUID, target: &UserUUID, expires_at: DateTime<Utc>) -> Challenge {
+        Challenge {
+            challenge_uuid: uuid::Uuid::new_v4().simple().to_string(),
+            challenger_uuid: challenger.clone(),
+            challenger_name: "Challenger".to_owned(),
+            target_user_uuid: target.clone(),
+            game_configuration: GameConfiguration {
+                creator_name: "Challenger".to_owned(),
+                opponent: PlayerType::Human,
+                side_selection: ColorPreference::Random,
+                time_control: None,
+                rated: false,
+                private: true,
+            },
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn only_the_target_answers() {
+        let (challenger, target) = (new_user(), new_user());
+        let now = Utc::now();
+        let later = now + chrono::Duration::minutes(5);
+        let mut book = ChallengeBook::default();
+
+        assert!(book
+            .issue(challenge(&challenger, &challenger, later))
+            .is_err());
+        let first = challenge(&challenger, &target, later);
+        book.issue(first.clone()).unwrap();
+        assert!(book.issue(challenge(&challenger, &target, later)).is_err());
+        assert_eq!(book.involving(&target).len(), 1);
+
+        let uuid = &first.challenge_uuid;
+        assert!(book.accept(uuid, &challenger, now).is_err());
+        assert!(book.cancel(uuid, &target).is_err());
+        assert!(book.accept(uuid, &target, now).is_ok());
+        assert!(book.decline(uuid, &target).is_err());
+        assert!(book.expire(uuid).is_none());
+
+        // A challenge past its expiry can no longer be accepted, even before it is swept
+        let stale = challenge(&challenger, &target, now);
+        book.issue(stale.clone()).unwrap();
+        assert!(book.accept(&stale.challenge_uuid, &target, now).is_err());
+        assert!(book.involving(&challenger).is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+use super::{server_config::ServerConfig, user_uuid::UserUUID};
+use crate::logic::player_side::PlayerSide;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+// Players write to [Players], which everyone reads. Spectators write to [Spectators], which
+// the players only get to read once the game is over.
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Copy, Debug, PartialEq,
+)]
+pub enum ChatChannel {
+    Players = 0,
+    Spectators = 1,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug)]
+pub struct ChatEntry {
+    pub channel: ChatChannel,
+    pub user_name: String,
+    pub side: Option<PlayerSide>, // None for spectators
+    pub text: String,
+    pub sent_at: DateTime<Utc>,
+}
+
+// A game's chat: the last [history] messages, and the limits every sender has to respect
+#[derive(Debug)]
+pub struct Chat {
+    entries: VecDeque<ChatEntry>,
+    history: usize,
+    max_length: usize,
+    messages_per_minute: usize,
+    sent_at: HashMap<UserUUID, VecDeque<Instant>>, // Each sender's messages within [RATE_WINDOW]
+}
+
+impl Chat {
+    pub fn new(config: &ServerConfig) -> Self {
+        Chat {
+            entries: VecDeque::new(),
+            history: config.chat_history,
+            max_length: config.chat_max_length,
+            messages_per_minute: config.chat_messages_per_minute,
+            sent_at: HashMap::new(),
+        }
+    }
+
+    // Checks the message against the limits for [sender], and records it if it passes
+    pub fn post(
+        &mut self,
+        sender: &UserUUID,
+        entry: ChatEntry,
+        now: Instant,
+    ) -> Result<(), &'static str> {
+        if entry.text.trim().is_empty() {
+            return Err("The message is empty");
+        }
+        if entry.text.chars().count() > self.max_length {
+            return Err("The message is too long");
+        }
+
+        let sent_at = self.sent_at.entry(sender.clone()).or_default();
+        while sent_at.front().is_some_and(|&at| at + RATE_WINDOW <= now) {
+            sent_at.pop_front();
+        }
+        if sent_at.len() >= self.messages_per_minute {
+            return Err("You are sending messages too fast");
+        }
+        sent_at.push_back(now);
+
+        if self.entries.len() >= self.history {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        Ok(())
+    }
+
+    // The recorded messages from the channels [can_read] accepts, oldest first
+    pub fn history(&self, can_read: impl Fn(ChatChannel) -> bool) -> Vec<ChatEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| can_read(entry.channel))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(channel: ChatChannel, text: &str) -> ChatEntry {
+        ChatEntry {
+            channel,
+            user_name: "Chatty".to_owned(),
+            side: None,
+            text: text.to_owned(),
+            sent_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn limits_and_history() {
+        let config = ServerConfig {
+            chat_history: 2,
+            chat_max_length: 5,
+            chat_messages_per_minute: 2,
+            ..ServerConfig::default()
+        };
+        let mut chat = Chat::new(&config);
+        let sender = UserUUID::new(uuid::Uuid::new_v4().to_string()).unwrap();
+        let start = Instant::now();
+
+        assert!(chat
+            .post(&sender, entry(ChatChannel::Players, "  "), start)
+            .is_err());
+        assert!(chat
+            .post(&sender, entry(ChatChannel::Players, "hello!"), start)
+            .is_err());
+        chat.post(&sender, entry(ChatChannel::Players, "hi"), start)
+            .unwrap();
+        chat.post(&sender, entry(ChatChannel::Spectators, "gg"), start)
+            .unwrap();
+        assert!(chat
+            .post(&sender, entry(ChatChannel::Players, "wait"), start)
+            .is_err());
+
+        // The oldest message makes room once the window has passed
+        let later = start + RATE_WINDOW;
+        chat.post(&sender, entry(ChatChannel::Players, "ok"), later)
+            .unwrap();
+        let texts: Vec<String> = chat.history(|_| true).into_iter().map(|e| e.text).collect();
+        assert_eq!(texts, ["gg", "ok"]);
+        assert_eq!(chat.history(|c| c == ChatChannel::Players).len(), 1);
+    }
+}
@@ -3,12 +3,35 @@ use tokio::sync::{mpsc, Mutex};
 use warp::filters::ws::Message;
 
 use super::user_uuid::UserUUID;
+use crate::protocol::{handshake::Capability, response::Response};
+
+pub type ClientSender = mpsc::UnboundedSender<std::result::Result<Message, warp::Error>>;
 
 #[derive(Debug, Clone)]
 pub struct Client {
     pub user_name: String,
     pub user_uuid: UserUUID,
     pub user_avatar_uuid: String,
-    pub sender: Option<mpsc::UnboundedSender<std::result::Result<Message, warp::Error>>>,
+    pub capabilities: Vec<Capability>, // Empty until the client says [Hello]
+    pub sender: Option<ClientSender>,
 }
 pub type Clients = Arc<Mutex<HashMap<UserUUID, Client>>>;
+
+// Clones the senders of the given clients, so that messages can be sent without holding the lock
+pub async fn get_senders(clients: &Clients, client_uuids: &[UserUUID]) -> Vec<ClientSender> {
+    clients
+        .lock()
+        .await
+        .iter()
+        .filter(|(uuid, _)| client_uuids.contains(uuid))
+        .filter_map(|(_, client)| client.sender.clone())
+        .collect()
+}
+
+// Sends [res] to the given clients only, outside of any game subscription
+pub async fn send_to(clients: &Clients, client_uuids: &[UserUUID], res: &Response) {
+    let msg = serde_json::to_string(res).unwrap();
+    for sender in get_senders(clients, client_uuids).await {
+        let _ = sender.send(Ok(Message::text(msg.clone())));
+    }
+}
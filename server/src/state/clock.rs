@@ -0,0 +1,172 @@
+use super::game_configuration::TimeControl;
+use crate::logic::player_side::PlayerSide;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+pub struct ClockState {
+    pub white: Duration,
+    pub black: Duration,
+    pub running: Option<PlayerSide>, // None before the game starts and after it ends
+}
+
+// A chess clock with Fischer increment: the side to move is charged for the time it takes,
+// and gets the increment back once the turn passes to the opponent.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    white: Duration,
+    black: Duration,
+    increment: Duration,
+    running: Option<(PlayerSide, Instant)>, // Whose clock runs, and since when
+}
+
+impl Clock {
+    pub fn new(time_control: &TimeControl) -> Self {
+        Clock {
+            white: time_control.time,
+            black: time_control.time,
+            increment: time_control.increment,
+            running: None,
+        }
+    }
+
+    pub fn start(&mut self, side: PlayerSide, now: Instant) {
+        self.running = Some((side, now));
+    }
+
+    pub fn stop(&mut self, now: Instant) {
+        if let Some((side, since)) = self.running.take() {
+            let time_left = self.time_left_mut(side);
+            *time_left = time_left.saturating_sub(now.saturating_duration_since(since));
+        }
+    }
+
+    // Charge [mover] for its move; the increment is only added if the turn passes
+    pub fn punch(&mut self, mover: PlayerSide, next: PlayerSide, now: Instant) {
+        self.stop(now);
+        if mover != next {
+            let increment = self.increment;
+            let time_left = self.time_left_mut(mover);
+            *time_left = time_left.saturating_add(increment);
+        }
+        self.start(next, now);
+    }
+
+    pub fn time_left(&self, side: PlayerSide, now: Instant) -> Duration {
+        let time_left = match side {
+            PlayerSide::White => self.white,
+            PlayerSide::Black => self.black,
+        };
+        match self.running {
+            Some((running, since)) if running == side => {
+                time_left.saturating_sub(now.saturating_duration_since(since))
+            }
+            _ => time_left,
+        }
+    }
+
+    // The side whose time has run out, if any
+    pub fn flagged(&self, now: Instant) -> Option<PlayerSide> {
+        let (side, _) = self.running?;
+        self.time_left(side, now).is_zero().then_some(side)
+    }
+
+    // When the running side's time runs out; None also if that is beyond what [Instant] holds
+    pub fn deadline(&self) -> Option<Instant> {
+        let (side, since) = self.running?;
+        since.checked_add(self.time_left(side, since))
+    }
+
+    pub fn state(&self, now: Instant) -> ClockState {
+        ClockState {
+            white: self.time_left(PlayerSide::White, now),
+            black: self.time_left(PlayerSide::Black, now),
+            running: self.running.map(|(side, _)| side),
+        }
+    }
+
+    // Sets both clocks back to an earlier [state], with [side]'s running from [now]
+    pub fn restore(&mut self, state: &ClockState, side: PlayerSide, now: Instant) {
+        self.white = state.white;
+        self.black = state.black;
+        self.start(side, now);
+    }
+
+    fn time_left_mut(&mut self, side: PlayerSide) -> &mut Duration {
+        match side {
+            PlayerSide::White => &mut self.white,
+            PlayerSide::Black => &mut self.black,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock() -> Clock {
+        Clock::new(&TimeControl {
+            time: Duration::from_secs(60),
+            increment: Duration::from_secs(2),
+        })
+    }
+
+    #[test]
+    fn fischer_increment() {
+        let start = Instant::now();
+        let mut clock = clock();
+        clock.start(PlayerSide::White, start);
+
+        clock.punch(
+            PlayerSide::White,
+            PlayerSide::Black,
+            start + Duration::from_secs(10),
+        );
+        assert_eq!(
+            clock.time_left(PlayerSide::White, start),
+            Duration::from_secs(52)
+        );
+
+        // A take-back keeps the turn, so there is no increment
+        clock.punch(
+            PlayerSide::Black,
+            PlayerSide::Black,
+            start + Duration::from_secs(15),
+        );
+        assert_eq!(
+            clock.time_left(PlayerSide::Black, start),
+            Duration::from_secs(55)
+        );
+        assert_eq!(clock.state(start).running, Some(PlayerSide::Black));
+    }
+
+    #[test]
+    fn flag_fall() {
+        let start = Instant::now();
+        let mut clock = clock();
+        assert_eq!(clock.deadline(), None);
+
+        clock.start(PlayerSide::White, start);
+        assert_eq!(clock.deadline(), Some(start + Duration::from_secs(60)));
+        assert_eq!(clock.flagged(start + Duration::from_secs(59)), None);
+        assert_eq!(
+            clock.flagged(start + Duration::from_secs(60)),
+            Some(PlayerSide::White)
+        );
+    }
+
+    #[test]
+    fn huge_time_controls_do_not_overflow() {
+        let start = Instant::now();
+        let mut clock = Clock::new(&TimeControl {
+            time: Duration::MAX,
+            increment: Duration::MAX,
+        });
+        clock.start(PlayerSide::White, start);
+        assert_eq!(clock.deadline(), None);
+
+        clock.punch(PlayerSide::White, PlayerSide::Black, start);
+        assert_eq!(clock.time_left(PlayerSide::White, start), Duration::MAX);
+        assert_eq!(clock.flagged(start), None);
+    }
+}
@@ -1,136 +1,670 @@
 use super::{
-    client::Clients,
-    game_configuration::{ColorPreference, GameConfiguration, PlayerType},
-    game_meta::GameMeta,
+    analysis_pool::AnalysisPool,
+    archive::GameRecord,
+    bot::Bots,
+    chat::{Chat, ChatChannel, ChatEntry},
+    client::{send_to, Clients},
+    clock::{Clock, ClockState},
+    game_configuration::{ColorPreference, GameConfiguration, PlayerType, TimeControl},
+    game_meta::{GameMeta, GameResult, GameState, Termination},
     game_uuid::GameUUID,
+    lobby::{GameSummary, Lobby},
+    rating::{PlayerRating, Ratings},
+    server_state::ServerState,
     user_uuid::UserUUID,
 };
 use crate::{
     logic::{
-        ai::AI,
         amove::Move,
         board::{Board, BoardFrontend},
+        evaluation::Evaluation,
+        opening_book::OpeningBook,
         player_side::PlayerSide,
     },
     protocol::response::Response,
 };
+use chrono::Utc;
 use rand::Rng;
-use std::{collections::HashMap, sync::Arc};
-use tokio::{spawn, sync::Mutex, task};
-use warp::filters::ws::Message;
+use std::{collections::HashMap, slice, sync::Arc, time::Duration};
+use tokio::{sync::broadcast, time::Instant};
 
-#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+// The players' remaining time is kept by the game's [Clock]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone)]
 pub struct Player {
-    time_left: (), // TODO
     player_type: PlayerType,
 }
 
 impl Player {
     pub fn new_computer() -> Self {
         Player {
-            time_left: (),
             player_type: PlayerType::Computer,
         }
     }
 
     pub fn new_human() -> Self {
-        Player {
-            time_left: (),
-            player_type: PlayerType::Human,
-        }
+        Player::new(PlayerType::Human)
+    }
+
+    pub fn new(player_type: PlayerType) -> Self {
+        Player { player_type }
     }
+
+    pub fn player_type(&self) -> &PlayerType {
+        &self.player_type
+    }
+}
+
+// Players take a seat; spectators only watch
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq,
+)]
+pub enum Role {
+    Player = 0,
+    Spectator = 1,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PlayerAction {
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+    Abort,
+    RequestTakeback,
+    AcceptTakeback,
+    DeclineTakeback,
 }
 
 #[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub board: Board, // The position before the move
+    pub mv: Move,
+    pub clock: Option<ClockState>, // Both clocks as the move was made, before the increment
+}
+
+// The state of a single game. It is owned by the game's task (see [game_handle]), which is the
+// only place that mutates it, so none of the fields need to be locked.
+#[derive(Debug)]
 pub struct Game {
     _creator_uuid: UserUUID,
     game_uuid: GameUUID,
     clients: Clients,
+    events: broadcast::Sender<Response>,
+    lobby: Lobby,
+    ratings: Ratings,
+    opening_book: Arc<OpeningBook>,
+    evaluation: Evaluation,
+    analysis: AnalysisPool,
+    bots: Bots,
 
     player_white: Option<(UserUUID, Player)>,
     player_black: Option<(UserUUID, Player)>,
-    spectators: Vec<UserUUID>,
-    board: Arc<Mutex<Board>>,
-
-    game_meta: Arc<Mutex<GameMeta>>, // Any change to the board updates the game's metadata, hence [Mutex]
+    spectators: Vec<UserUUID>, // Clients watching the game, players excluded
+    max_spectators: usize,
+    board: Board,
+    clock: Option<Clock>,                 // None if unlimited
+    draw_offer: Option<PlayerSide>, // The side that offered a draw, until the other side replies
+    takeback_request: Option<PlayerSide>, // The side that asked to undo its last turn
+    history: Vec<HistoryEntry>,
+    absent_since: HashMap<PlayerSide, Instant>, // Seated players whose connection dropped
+    reconnect_grace: Duration,
+    chat: Chat,
+    rating_changes: Option<(f64, f64)>, // White's and black's, once a rated game is over
+    annotated: bool,                    // Whether the game was handed to the analysis pool
+
+    game_meta: GameMeta, // Any change to the board updates the game's metadata
     game_configuration: GameConfiguration,
 }
-pub type Games = Arc<Mutex<HashMap<GameUUID, Game>>>;
 
 impl Game {
     pub fn new(
         game_uuid: GameUUID,
         client_uuid: UserUUID,
-        game_configuration: GameConfiguration,
-        clients: Clients,
+        mut game_configuration: GameConfiguration,
+        state: &ServerState,
     ) -> Game {
+        // Games against the computer are never rated
+        game_configuration.rated &= game_configuration.opponent == PlayerType::Human;
+        game_configuration.time_control = game_configuration
+            .time_control
+            .as_ref()
+            .map(TimeControl::clamped);
+        let config = &state.config;
+        let (events, _) = broadcast::channel(64);
+        let clock = game_configuration.time_control.as_ref().map(Clock::new);
         Game {
             _creator_uuid: client_uuid,
             game_uuid,
-            clients,
+            clients: state.clients.clone(),
+            events,
+            lobby: state.lobby.clone(),
+            ratings: state.ratings.clone(),
+            opening_book: Arc::clone(&state.opening_book),
+            evaluation: state.evaluation,
+            analysis: state.analysis.clone(),
+            bots: state.bots.clone(),
 
             player_white: None,
             player_black: None,
             spectators: vec![],
-            board: Arc::new(Mutex::new(Board::new())),
-
-            game_meta: Arc::new(Mutex::new(GameMeta::new_pending())),
+            max_spectators: config.max_spectators,
+            board: Board::new(),
+            clock,
+            draw_offer: None,
+            takeback_request: None,
+            history: vec![],
+            absent_since: HashMap::new(),
+            reconnect_grace: config.reconnect_grace,
+            chat: Chat::new(config),
+            rating_changes: None,
+            annotated: false,
+
+            game_meta: GameMeta::new_pending(),
             game_configuration,
         }
     }
 }
 
 impl Game {
-    pub async fn add_client(&mut self, client_uuid: UserUUID) {
-        if !self.spectators.contains(&client_uuid) {
-            self.add_spectator(client_uuid.clone());
-
-            if self.player_slot_is_available() {
-                self.add_player(client_uuid, Player::new_human());
-                if self.player_slots_are_taken() {
-                    self.game_meta.lock().await.promote_to_in_progress();
-                };
-                self.ping_ai().await;
+    // Seats the client if [role] allows it and a seat is free, otherwise adds it as a spectator.
+    // Returns the client's role; joining again is a no-op apart from resending the participants.
+    pub async fn add_client(
+        &mut self,
+        client_uuid: UserUUID,
+        role: Option<Role>,
+    ) -> Result<Role, &'static str> {
+        let joiner = client_uuid.clone();
+        let role = if self.is_player(&client_uuid) {
+            Role::Player
+        } else if role != Some(Role::Spectator) && self.seat_is_open() {
+            self.take_seat(client_uuid).await?;
+            self.lobby.game_updated(self.summary());
+            Role::Player
+        } else if role == Some(Role::Player) {
+            return Err("There is no free seat in this game");
+        } else {
+            if !self.spectators.contains(&client_uuid) {
+                if self.spectators.len() >= self.max_spectators {
+                    return Err("The game has reached its spectator limit");
+                }
+                self.spectators.push(client_uuid);
+            }
+            Role::Spectator
+        };
+        self.broadcast_participants().await;
+
+        // Catch up a late joiner on what happened so far
+        self.send_to(slice::from_ref(&joiner), &self.game_state())
+            .await;
+        self.send_chat_history(&joiner, role).await;
+        Ok(role)
+    }
+
+    // Seats both players of a game the server arranged, before it is published anywhere. The
+    // first player is seated first, so the configuration's side selection applies to them.
+    pub async fn seat_players(
+        &mut self,
+        first: UserUUID,
+        second: UserUUID,
+    ) -> Result<(), &'static str> {
+        self.take_seat(first).await?;
+        self.take_seat(second).await
+    }
+
+    // Spectators may leave at any time. Players may give up their seat until the game starts,
+    // and have to resign or abort it after that.
+    pub async fn remove_client(&mut self, client_uuid: &UserUUID) -> Result<(), &'static str> {
+        if self.spectators.contains(client_uuid) {
+            self.spectators.retain(|uuid| uuid != client_uuid);
+        } else if self.is_player(client_uuid) {
+            match self.game_meta.status {
+                GameState::Pending => {
+                    let seated_by = |slot: &Option<(UserUUID, Player)>| {
+                        slot.as_ref().is_some_and(|(uuid, _)| uuid == client_uuid)
+                    };
+                    if seated_by(&self.player_white) {
+                        self.player_white = None;
+                    }
+                    if seated_by(&self.player_black) {
+                        self.player_black = None;
+                    }
+                    self.lobby.game_updated(self.summary());
+                }
+                GameState::InProgress => return Err("Resign or abort the game before leaving"),
+                GameState::Completed => {}
             }
+        } else {
+            return Err("You are not in this game");
         }
         self.broadcast_participants().await;
+        Ok(())
     }
 
-    pub async fn emit_board(&self, client_uuid: &UserUUID) {
-        let res = Response::GameState {
+    pub fn game_state(&self) -> Response {
+        Response::GameState {
             game_uuid: self.game_uuid.clone(),
-            game_state: BoardFrontend::new(self.board.lock().await.clone()),
+            game_state: BoardFrontend::new(self.board.clone()),
+            clock: self.clock.as_ref().map(|clock| clock.state(Instant::now())),
+            legal_moves: self.board.legal_moves(),
+        }
+    }
+
+    pub fn preview_move(&self, mv: Move) -> Result<Response, &'static str> {
+        let mut board = self.board.clone();
+        board.make_move(mv)?;
+        Ok(Response::MovePreview {
+            game_uuid: self.game_uuid.clone(),
+            mv,
+            starts_take_back: !self.board.take_back_rule() && board.take_back_rule(),
+            legal_moves: board.legal_moves(),
+            position: BoardFrontend::new(board),
+        })
+    }
+
+    pub fn make_move(&mut self, client_uuid: &UserUUID, mv: Move) -> Result<(), &'static str> {
+        if !self.is_human_turn(client_uuid) {
+            return Err("It is not your turn");
+        }
+        self.apply_move(mv)
+    }
+
+    // The position the player asks a hint for. The search runs outside of the game, so the hint is
+    // only counted, with [count_hint], once it has been found.
+    pub fn hint_position(&self, client_uuid: &UserUUID) -> Result<Board, &'static str> {
+        self.human_side(client_uuid)
+            .ok_or("You are not playing this game")?;
+        if self.game_configuration.rated {
+            return Err("Hints are only available in casual games");
+        }
+        if self.game_meta.status != GameState::InProgress {
+            return Err("The game is not in progress");
+        }
+        if !self.is_human_turn(client_uuid) {
+            return Err("It is not your turn");
+        }
+        Ok(self.board.clone())
+    }
+
+    pub fn live_position(&self, client_uuid: &UserUUID) -> Option<Board> {
+        (self.is_player(client_uuid) && self.game_meta.status != GameState::Completed)
+            .then(|| self.board.clone())
+    }
+
+    pub fn count_hint(&mut self, client_uuid: &UserUUID) {
+        if let Some(side) = self.human_side(client_uuid) {
+            self.game_meta.count_hint(side);
+            self.lobby.game_updated(self.summary());
+        }
+    }
+
+    // Players write to the players' channel, spectators to the spectators' one
+    pub async fn send_chat(
+        &mut self,
+        client_uuid: &UserUUID,
+        text: String,
+    ) -> Result<(), &'static str> {
+        let (channel, side) = if self.is_player(client_uuid) {
+            (ChatChannel::Players, self.human_side(client_uuid))
+        } else if self.spectators.contains(client_uuid) {
+            (ChatChannel::Spectators, None)
+        } else {
+            return Err("You are not in this game");
+        };
+        let user_name = match self.clients.lock().await.get(client_uuid) {
+            Some(client) => client.user_name.clone(),
+            None => "Disconnected...".to_owned(),
         };
 
-        self.clients
-            .lock()
-            .await
-            .get(client_uuid)
-            .expect("Client UUID does not exist")
-            .sender
-            .iter()
-            .for_each(|sender| {
-                let _ = sender.send(Ok(Message::text(serde_json::to_string(&res).unwrap())));
+        let entry = ChatEntry {
+            channel,
+            user_name,
+            side,
+            text,
+            sent_at: Utc::now(),
+        };
+        self.chat.post(client_uuid, entry.clone(), Instant::now())?;
+        let res = Response::ChatMessage {
+            game_uuid: self.game_uuid.clone(),
+            message: entry,
+        };
+        if self.can_read(Role::Player, channel) {
+            self.broadcast(res);
+        } else {
+            self.send_to(&self.spectators, &res).await;
+        }
+        Ok(())
+    }
+
+    pub fn act(
+        &mut self,
+        client_uuid: &UserUUID,
+        action: PlayerAction,
+    ) -> Result<(), &'static str> {
+        let side = self
+            .human_side(client_uuid)
+            .ok_or("You are not playing this game")?;
+        if self.game_meta.result.is_some() {
+            return Err("The game is over");
+        }
+
+        let now = Instant::now();
+        match action {
+            PlayerAction::Resign => {
+                if self.game_meta.status != GameState::InProgress {
+                    return Err("The game has not started, abort it instead");
+                }
+                self.complete(
+                    GameResult {
+                        winner: Some(!side),
+                        termination: Termination::Resignation,
+                    },
+                    now,
+                );
+            }
+            PlayerAction::OfferDraw => {
+                if self.game_meta.status != GameState::InProgress {
+                    return Err("The game has not started");
+                }
+                if self.get_ai_color().is_some() {
+                    return Err("The computer does not accept draws");
+                }
+                match self.draw_offer {
+                    Some(offer) if offer == side => return Err("You have already offered a draw"),
+                    Some(_) => return self.act(client_uuid, PlayerAction::AcceptDraw),
+                    None => {
+                        self.draw_offer = Some(side);
+                        self.broadcast(Response::DrawOffered {
+                            game_uuid: self.game_uuid.clone(),
+                            side,
+                        });
+                    }
+                }
+            }
+            PlayerAction::AcceptDraw => {
+                if self.draw_offer != Some(!side) {
+                    return Err("There is no draw offer to accept");
+                }
+                self.complete(
+                    GameResult {
+                        winner: None,
+                        termination: Termination::Agreement,
+                    },
+                    now,
+                );
+            }
+            PlayerAction::DeclineDraw => {
+                if self.draw_offer != Some(!side) {
+                    return Err("There is no draw offer to decline");
+                }
+                self.draw_offer = None;
+                self.broadcast(Response::DrawDeclined {
+                    game_uuid: self.game_uuid.clone(),
+                    side,
+                });
+            }
+            PlayerAction::Abort => {
+                if self.board.get_move_number() > 0 {
+                    return Err("The game can only be aborted before the first move");
+                }
+                self.complete(
+                    GameResult {
+                        winner: None,
+                        termination: Termination::Aborted,
+                    },
+                    now,
+                );
+            }
+            PlayerAction::RequestTakeback => {
+                let turn_start = self.last_turn_start(side).ok_or("You have not moved yet")?;
+                if self.get_ai_color().is_some() {
+                    // The computer always agrees, and its reply is undone together with the turn
+                    self.rewind(turn_start, now);
+                    return Ok(());
+                }
+                if self.takeback_request.is_some() {
+                    return Err("A takeback has already been requested");
+                }
+                self.takeback_request = Some(side);
+                self.broadcast(Response::TakebackRequested {
+                    game_uuid: self.game_uuid.clone(),
+                    side,
+                });
+            }
+            PlayerAction::AcceptTakeback => {
+                if self.takeback_request != Some(!side) {
+                    return Err("There is no takeback request to accept");
+                }
+                let turn_start = self
+                    .last_turn_start(!side)
+                    .ok_or("There is no move to take back")?;
+                self.rewind(turn_start, now);
+            }
+            PlayerAction::DeclineTakeback => {
+                if self.takeback_request != Some(!side) {
+                    return Err("There is no takeback request to decline");
+                }
+                self.takeback_request = None;
+                self.broadcast(Response::TakebackDeclined {
+                    game_uuid: self.game_uuid.clone(),
+                    side,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // The side the AI has to move for, if it is the AI's turn
+    pub fn ai_to_move(&self) -> Option<PlayerSide> {
+        let ai_side = self.get_ai_color()?;
+        let ai_turn = self.player_slots_are_taken()
+            && self.game_meta.result.is_none()
+            && self.board.get_turn() == ai_side;
+        ai_turn.then_some(ai_side)
+    }
+
+    pub fn apply_ai_move(&mut self, mv: Move) -> Result<(), &'static str> {
+        self.apply_move(mv)
+    }
+
+    // The client's connection dropped; a spectator leaves, a seated player has [reconnect_grace]
+    // to come back
+    pub async fn disconnect(&mut self, client_uuid: &UserUUID) {
+        if self.spectators.contains(client_uuid) {
+            self.spectators.retain(|uuid| uuid != client_uuid);
+            self.broadcast_participants().await;
+            return;
+        }
+        if self.game_meta.status != GameState::InProgress {
+            return;
+        }
+        for side in self.human_sides(client_uuid) {
+            self.absent_since.insert(side, Instant::now());
+            self.broadcast(Response::Presence {
+                game_uuid: self.game_uuid.clone(),
+                side,
+                online: false,
             });
+        }
+        self.broadcast_participants().await;
+    }
+
+    // Returns whether [client_uuid] plays the game, in which case everything a (re)subscribed
+    // client needs is broadcast again. Spectators left when they disconnected.
+    pub async fn reconnect(&mut self, client_uuid: &UserUUID) -> bool {
+        if !self.is_player(client_uuid) || self.game_meta.status == GameState::Completed {
+            return false;
+        }
+        for side in self.human_sides(client_uuid) {
+            if self.absent_since.remove(&side).is_some() {
+                self.broadcast(Response::Presence {
+                    game_uuid: self.game_uuid.clone(),
+                    side,
+                    online: true,
+                });
+            }
+        }
+        self.broadcast_participants().await;
+        self.broadcast_board();
+        self.send_chat_history(client_uuid, Role::Player).await;
+        true
+    }
+
+    // Ends a game the janitor considers stale: a pending game is aborted, a game in progress
+    // is lost by the side that stopped moving
+    pub fn expire(&mut self) {
+        let now = Instant::now();
+        match self.game_meta.status {
+            GameState::Pending => self.complete(
+                GameResult {
+                    winner: None,
+                    termination: Termination::Aborted,
+                },
+                now,
+            ),
+            GameState::InProgress => self.complete(
+                GameResult {
+                    winner: Some(!self.board.get_turn()),
+                    termination: Termination::Abandoned,
+                },
+                now,
+            ),
+            GameState::Completed => {}
+        }
+    }
+
+    pub fn record(&self) -> GameRecord {
+        GameRecord {
+            game_uuid: self.game_uuid.clone(),
+            game_meta: self.game_meta.clone(),
+            game_configuration: self.game_configuration.clone(),
+            player_white: self.player_white.clone(),
+            player_black: self.player_black.clone(),
+            moves: self.history.iter().map(|entry| entry.mv).collect(),
+            chat: self.chat.history(|_| true),
+        }
+    }
+
+    // Feeds the result of a rated game into the players' ratings, once the game is over
+    pub async fn rate(&mut self) {
+        let Some(result) = &self.game_meta.result else {
+            return;
+        };
+        if !self.game_configuration.rated
+            || self.rating_changes.is_some()
+            || result.termination == Termination::Aborted
+        {
+            return;
+        }
+        let (Some((white, _)), Some((black, _))) = (&self.player_white, &self.player_black) else {
+            return;
+        };
+
+        let changes = self.ratings.write().await.record_game(
+            white,
+            black,
+            self.game_configuration.time_control_category(),
+            result.winner,
+        );
+        self.rating_changes = Some(changes);
+        self.broadcast_participants().await;
+    }
+
+    // Has the game annotated once it is over; aborted games have nothing to annotate
+    pub fn annotate(&mut self) {
+        let Some(result) = &self.game_meta.result else {
+            return;
+        };
+        if self.annotated || result.termination == Termination::Aborted || self.history.is_empty() {
+            return;
+        }
+        self.annotated = true;
+        let moves = self.history.iter().map(|entry| entry.mv).collect();
+        self.analysis.annotate_later(self.game_uuid.clone(), moves);
+    }
+
+    // The next moment the game may end on its own: a flag fall or an abandoned seat
+    pub fn deadline(&self) -> Option<Instant> {
+        let clock_deadline = self.clock.as_ref().and_then(|clock| clock.deadline());
+        let abandon_deadline = self
+            .absent_since
+            .values()
+            .min()
+            .map(|&since| since + self.reconnect_grace);
+        clock_deadline.into_iter().chain(abandon_deadline).min()
     }
 
-    pub async fn make_move(&mut self, _client_uuid: &UserUUID, mv: Move) {
-        // TODO: validate [client_uuid] has rights to make a move
+    pub fn check_deadlines(&mut self) {
+        self.check_flag();
+        self.check_abandonment();
+    }
+
+    // Ends the game if a player stayed away for longer than [reconnect_grace]
+    fn check_abandonment(&mut self) {
+        let now = Instant::now();
+        let abandoned: Vec<PlayerSide> = self
+            .absent_since
+            .iter()
+            .filter(|(_, &since)| since + self.reconnect_grace <= now)
+            .map(|(&side, _)| side)
+            .collect();
+        if abandoned.is_empty() || self.game_meta.result.is_some() {
+            return;
+        }
+
+        // If both players are gone, nobody wins
+        let winner = match abandoned[..] {
+            [side] => Some(!side),
+            _ => None,
+        };
+        self.complete(
+            GameResult {
+                winner,
+                termination: Termination::Abandoned,
+            },
+            now,
+        );
+    }
 
-        // Execute the move on the board and update the game's meta
-        let _ = self.board.lock().await.make_move(mv);
-        self.game_meta.lock().await.update_last_move_at();
-        if self.board.lock().await.is_game_over() {
-            self.game_meta.lock().await.promote_to_completed();
+    // Ends the game if the side to move ran out of time
+    fn check_flag(&mut self) {
+        let now = Instant::now();
+        if let Some(side) = self.clock.as_ref().and_then(|clock| clock.flagged(now)) {
+            self.complete(
+                GameResult {
+                    winner: Some(!side),
+                    termination: Termination::Timeout,
+                },
+                now,
+            );
         }
-        self.broadcast_board().await;
+    }
+
+    pub fn summary(&self) -> GameSummary {
+        (
+            self.game_uuid.clone(),
+            self.game_meta.clone(),
+            self.game_configuration.clone(),
+        )
+    }
+
+    pub fn events(&self) -> broadcast::Sender<Response> {
+        self.events.clone()
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn opening_book(&self) -> &Arc<OpeningBook> {
+        &self.opening_book
+    }
 
-        // AI broadcasts the board and updates the game's meta, if needed
-        self.ping_ai().await;
+    pub fn evaluation(&self) -> &Evaluation {
+        &self.evaluation
     }
 
-    pub async fn get_meta(&self) -> GameMeta {
-        self.game_meta.lock().await.clone()
+    pub fn get_meta(&self) -> &GameMeta {
+        &self.game_meta
     }
 
     pub fn get_description(&self) -> &GameConfiguration {
@@ -147,6 +681,109 @@ impl Game {
         self.player_white.is_some() && self.player_black.is_some()
     }
 
+    fn seat_is_open(&self) -> bool {
+        self.game_meta.status == GameState::Pending && self.player_slot_is_available()
+    }
+
+    fn can_read(&self, role: Role, channel: ChatChannel) -> bool {
+        channel == ChatChannel::Players
+            || role == Role::Spectator
+            || self.game_meta.status == GameState::Completed
+    }
+
+    // Takes a free seat without telling the lobby; the callers publish the change
+    async fn take_seat(&mut self, client_uuid: UserUUID) -> Result<(), &'static str> {
+        // Bots are not rated, so they cannot take a seat in a rated game, connected or not
+        let player_type = if self.bots.read().await.is_bot(&client_uuid) {
+            PlayerType::Bot
+        } else {
+            PlayerType::Human
+        };
+        if player_type == PlayerType::Bot && self.game_configuration.rated {
+            return Err("Bots may not play rated games");
+        }
+        self.spectators.retain(|uuid| uuid != &client_uuid);
+        self.add_player(client_uuid, Player::new(player_type));
+        if self.player_slots_are_taken() {
+            self.game_meta.promote_to_in_progress();
+            if let Some(clock) = &mut self.clock {
+                clock.start(self.board.get_turn(), Instant::now());
+            }
+        };
+        Ok(())
+    }
+
+    fn is_player(&self, client_uuid: &UserUUID) -> bool {
+        [&self.player_white, &self.player_black]
+            .into_iter()
+            .flatten()
+            .any(|(uuid, _)| uuid == client_uuid)
+    }
+
+    // Bots play through the same requests as humans
+    fn is_human_turn(&self, client_uuid: &UserUUID) -> bool {
+        let player = match self.board.get_turn() {
+            PlayerSide::White => &self.player_white,
+            PlayerSide::Black => &self.player_black,
+        };
+        match player {
+            Some((uuid, player)) => {
+                uuid == client_uuid && player.player_type != PlayerType::Computer
+            }
+            None => false,
+        }
+    }
+
+    // The side played by [client_uuid]; in computer games both seats belong to the same user
+    fn human_side(&self, client_uuid: &UserUUID) -> Option<PlayerSide> {
+        self.human_sides(client_uuid).into_iter().next()
+    }
+
+    // Both sides, if someone plays against themselves
+    fn human_sides(&self, client_uuid: &UserUUID) -> Vec<PlayerSide> {
+        let is_human = |slot: &Option<(UserUUID, Player)>| {
+            slot.as_ref().is_some_and(|(uuid, player)| {
+                uuid == client_uuid && player.player_type != PlayerType::Computer
+            })
+        };
+        [
+            (PlayerSide::White, &self.player_white),
+            (PlayerSide::Black, &self.player_black),
+        ]
+        .into_iter()
+        .filter(|(_, slot)| is_human(slot))
+        .map(|(side, _)| side)
+        .collect()
+    }
+
+    // Index in [history] of the first move of [side]'s last turn. A turn may consist of several
+    // moves, as the take-back phase and a skipped opponent keep the turn with the same side.
+    fn last_turn_start(&self, side: PlayerSide) -> Option<usize> {
+        let last = self
+            .history
+            .iter()
+            .rposition(|entry| entry.board.get_turn() == side)?;
+        let start = self.history[..last]
+            .iter()
+            .rposition(|entry| entry.board.get_turn() != side)
+            .map_or(0, |i| i + 1);
+        Some(start)
+    }
+
+    // Both clocks go back to where they stood when the first undone move was made, so the
+    // increments earned since are taken back too
+    fn rewind(&mut self, turn_start: usize, now: Instant) {
+        let entry = &self.history[turn_start];
+        self.board = entry.board.clone();
+        if let (Some(clock), Some(saved)) = (&mut self.clock, &entry.clock) {
+            clock.restore(saved, self.board.get_turn(), now);
+        }
+        self.history.truncate(turn_start);
+        self.draw_offer = None;
+        self.takeback_request = None;
+        self.broadcast_board();
+    }
+
     fn get_ai_color(&self) -> Option<PlayerSide> {
         if let Some((_, player)) = &self.player_white {
             if player.player_type == PlayerType::Computer {
@@ -160,150 +797,138 @@ impl Game {
         };
         None
     }
+
+    // Execute the move on the board, charge the mover's clock and update the game's meta
+    fn apply_move(&mut self, mv: Move) -> Result<(), &'static str> {
+        self.check_flag();
+        if self.game_meta.result.is_some() {
+            return Err("The game is over");
+        }
+        // Until both seats are taken nobody moves, so no clock runs for an empty seat
+        if self.game_meta.status != GameState::InProgress {
+            return Err("The game has not started");
+        }
+
+        let now = Instant::now();
+        let mover = self.board.get_turn();
+        let board = self.board.clone();
+        self.board.make_move(mv)?;
+        let clock = self.clock.as_ref().map(|clock| clock.state(now));
+        self.history.push(HistoryEntry { board, mv, clock });
+        self.takeback_request = None;
+        if let Some(clock) = &mut self.clock {
+            clock.punch(mover, self.board.get_turn(), now);
+        }
+
+        // A move by the opponent implicitly declines a pending draw offer
+        if self.draw_offer == Some(!mover) {
+            self.draw_offer = None;
+        }
+
+        self.game_meta.update_last_move_at();
+        self.broadcast_board();
+        match self.board.get_winner() {
+            Some(winner) => self.complete(
+                GameResult {
+                    winner: Some(winner),
+                    termination: Termination::Apex,
+                },
+                now,
+            ),
+            None => self.lobby.game_updated(self.summary()),
+        }
+        Ok(())
+    }
+
+    fn complete(&mut self, result: GameResult, now: Instant) {
+        if let Some(clock) = &mut self.clock {
+            clock.stop(now);
+        }
+        self.draw_offer = None;
+        self.takeback_request = None;
+        self.absent_since.clear();
+        self.game_meta.promote_to_completed(result.clone());
+        self.lobby.game_updated(self.summary());
+        self.broadcast(Response::GameOver {
+            game_uuid: self.game_uuid.clone(),
+            result,
+        });
+    }
 }
 
 impl Game {
+    fn broadcast(&self, res: Response) {
+        // Fails only if nobody is subscribed, which is fine
+        let _ = self.events.send(res);
+    }
+
     async fn broadcast_participants(&self) {
-        let clients_guard = self.clients.lock().await;
-
-        let player_white = if let Some((uuid, player)) = &self.player_white {
-            if let Some(client) = clients_guard.get(uuid) {
-                Some((
-                    client.user_name.clone(),
-                    client.user_avatar_uuid.clone(),
-                    player.clone(),
-                ))
-            } else {
-                Some((
-                    "Disconnected...".to_owned(),
-                    "xxxx".to_owned(),
-                    player.clone(),
-                ))
-            }
-        } else {
-            None
+        let (player_white, player_black) = {
+            let clients_guard = self.clients.lock().await;
+            let describe = |slot: &Option<(UserUUID, Player)>| {
+                slot.as_ref()
+                    .map(|(uuid, player)| match clients_guard.get(uuid) {
+                        Some(client) => (
+                            client.user_name.clone(),
+                            client.user_avatar_uuid.clone(),
+                            player.clone(),
+                        ),
+                        None => (
+                            "Disconnected...".to_owned(),
+                            "xxxx".to_owned(),
+                            player.clone(),
+                        ),
+                    })
+            };
+            (describe(&self.player_white), describe(&self.player_black))
         };
 
-        let player_black = if let Some((uuid, player)) = &self.player_black {
-            if let Some(client) = clients_guard.get(uuid) {
-                Some((
-                    client.user_name.clone(),
-                    client.user_avatar_uuid.clone(),
-                    player.clone(),
-                ))
-            } else {
-                Some((
-                    "Disconnected...".to_owned(),
-                    "xxxx".to_owned(),
-                    player.clone(),
-                ))
-            }
-        } else {
-            None
+        let (rating_white, rating_black) = {
+            let ratings_guard = self.ratings.read().await;
+            let pool = self.game_configuration.time_control_category();
+            let describe = |slot: &Option<(UserUUID, Player)>, change: Option<f64>| {
+                let (uuid, player) = slot.as_ref()?;
+                (player.player_type == PlayerType::Human).then(|| {
+                    let rating = ratings_guard.get(uuid, pool);
+                    PlayerRating {
+                        rating: rating.rating,
+                        provisional: rating.is_provisional(),
+                        change,
+                    }
+                })
+            };
+            let (white_change, black_change) = self.rating_changes.unzip();
+            (
+                describe(&self.player_white, white_change),
+                describe(&self.player_black, black_change),
+            )
         };
 
-        let res = Response::GameParticipants {
+        self.broadcast(Response::GameParticipants {
             game_uuid: self.game_uuid.clone(),
             player_white,
             player_black,
-        };
-
-        clients_guard
-            .iter()
-            .filter(|(uuid, _)| self.spectators.contains(uuid))
-            .for_each(|(_, client)| {
-                if let Some(sender) = &client.sender {
-                    let _ = sender.send(Ok(Message::text(serde_json::to_string(&res).unwrap())));
-                }
-            });
-    }
-
-    async fn broadcast_board(&self) {
-        let res: Response = Response::GameState {
-            game_uuid: self.game_uuid.clone(),
-            game_state: BoardFrontend::new(self.board.lock().await.clone()),
-        };
-
-        self.clients
-            .lock()
-            .await
-            .iter_mut()
-            .filter(|(uuid, _)| self.spectators.contains(uuid))
-            .for_each(|(_, client)| {
-                if let Some(sender) = &client.sender {
-                    let _ = sender.send(Ok(Message::text(serde_json::to_string(&res).unwrap())));
-                }
-            });
+            rating_white,
+            rating_black,
+            spectators: self.spectators.len(),
+        });
     }
 
-    async fn opponent_type_turn(&self) -> Option<PlayerType> {
-        match self.board.lock().await.get_turn() {
-            PlayerSide::White => match self.player_white.clone() {
-                Some(player) => Some(player.1.player_type),
-                None => None,
-            },
-            PlayerSide::Black => match self.player_black.clone() {
-                Some(player) => Some(player.1.player_type),
-                None => None,
-            },
-        }
+    fn broadcast_board(&self) {
+        self.broadcast(self.game_state());
     }
 
-    async fn ping_ai(&mut self) {
-        if let Some(PlayerType::Computer) = self.opponent_type_turn().await {
-            self.trigger_ai_move();
-        }
+    // Sends to the given clients only, bypassing the game's subscribers
+    async fn send_to(&self, client_uuids: &[UserUUID], res: &Response) {
+        send_to(&self.clients, client_uuids, res).await;
     }
 
-    fn trigger_ai_move(&mut self) {
-        let ai_side = self.get_ai_color().expect("No AI found");
-        let game_uuid = self.game_uuid.clone();
-
-        let clients = Arc::clone(&self.clients);
-        let board_clone = Arc::clone(&self.board);
-        let game_meta = Arc::clone(&self.game_meta);
-        let spectators_clone = self.spectators.clone();
-
-        spawn(async move {
-            let mut board_guard = board_clone.lock().await;
-
-            while board_guard.get_turn() == ai_side && !board_guard.is_game_over() {
-                let mut ai = AI {
-                    side: ai_side,
-                    board: board_guard.clone(),
-                };
-                if let Some(new_board) = ai.make_minmax_move() {
-                    *board_guard = new_board;
-                } else {
-                    break;
-                }
-
-                let res: Response = Response::GameState {
-                    game_uuid: game_uuid.clone(),
-                    game_state: BoardFrontend::new(board_guard.clone()),
-                };
-
-                // TODO?: remove duplication
-                clients
-                    .lock()
-                    .await
-                    .iter_mut()
-                    .filter(|(uuid, _)| spectators_clone.contains(uuid))
-                    .for_each(|(_, client)| {
-                        if let Some(sender) = &client.sender {
-                            let _ = sender
-                                .send(Ok(Message::text(serde_json::to_string(&res).unwrap())));
-                        }
-                    });
-                task::yield_now().await;
-            }
-
-            let mut meta_guard = game_meta.lock().await;
-            meta_guard.update_last_move_at();
-            if board_guard.is_game_over() {
-                meta_guard.promote_to_completed();
-            }
-        });
+    async fn send_chat_history(&self, client_uuid: &UserUUID, role: Role) {
+        let res = Response::ChatHistory {
+            game_uuid: self.game_uuid.clone(),
+            messages: self.chat.history(|channel| self.can_read(role, channel)),
+        };
+        self.send_to(slice::from_ref(client_uuid), &res).await;
     }
 
     fn add_with_color_pref(
@@ -352,8 +977,4 @@ impl Game {
             }
         };
     }
-
-    fn add_spectator(&mut self, client_uuid: UserUUID) {
-        self.spectators.push(client_uuid)
-    }
 }
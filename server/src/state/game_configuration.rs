@@ -1,28 +1,98 @@
 use std::time::Duration;
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
 pub enum PlayerType {
     Human = 0,
     Computer = 1,
+    Bot = 2, // An external program playing through the bot API
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Copy)]
+#[derive(
+    serde::Deserialize,
+    serde::Serialize,
+    schemars::JsonSchema,
+    Clone,
+    Debug,
+    Copy,
+    Default,
+    PartialEq,
+)]
 pub enum ColorPreference {
     AlwaysWhite = 0,
     AlwaysBlack = 1,
+    #[default]
     Random = 2,
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
 pub struct TimeControl {
     pub time: Duration,
     pub increment: Duration,
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+#[derive(
+    serde::Deserialize,
+    serde::Serialize,
+    schemars::JsonSchema,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+)]
+pub enum TimeControlCategory {
+    Unlimited = 0,
+    Bullet = 1,
+    Blitz = 2,
+    Rapid = 3,
+    Classical = 4,
+}
+
+impl TimeControl {
+    // Clocks never hold more than this, which keeps their arithmetic far from overflowing
+    pub const MAX_TIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+    pub const MAX_INCREMENT: Duration = Duration::from_secs(60 * 60);
+
+    pub fn is_in_range(&self) -> bool {
+        self.time <= Self::MAX_TIME && self.increment <= Self::MAX_INCREMENT
+    }
+
+    pub fn clamped(&self) -> TimeControl {
+        TimeControl {
+            time: self.time.min(Self::MAX_TIME),
+            increment: self.increment.min(Self::MAX_INCREMENT),
+        }
+    }
+
+    // Categorized by the estimated duration of a game, assuming 40 moves per side
+    pub fn category(&self) -> TimeControlCategory {
+        let estimated = self.time.saturating_add(self.increment.saturating_mul(40));
+        match estimated.as_secs() {
+            0..=179 => TimeControlCategory::Bullet,
+            180..=479 => TimeControlCategory::Blitz,
+            480..=1499 => TimeControlCategory::Rapid,
+            _ => TimeControlCategory::Classical,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug)]
 pub struct GameConfiguration {
     pub creator_name: String,
     pub opponent: PlayerType,
     pub side_selection: ColorPreference,
     pub time_control: Option<TimeControl>, // None if unlimited
+    #[serde(default)]
+    pub rated: bool,  // Only games between two humans are rated
+    #[serde(default)]
+    pub private: bool, // Challenge games are kept out of the lobby
+}
+
+impl GameConfiguration {
+    pub fn time_control_category(&self) -> TimeControlCategory {
+        self.time_control
+            .as_ref()
+            .map_or(TimeControlCategory::Unlimited, TimeControl::category)
+    }
 }
@@ -0,0 +1,895 @@
+use super::{
+    archive::GameRecord,
+    game::{Game, PlayerAction, Role},
+    game_configuration::GameConfiguration,
+    game_uuid::GameUUID,
+    lobby::GameSummary,
+    server_state::ServerState,
+    user_uuid::UserUUID,
+};
+use crate::{
+    logic::{
+        ai::{AI, DEFAULT_FUEL},
+        amove::Move,
+        board::Board,
+    },
+    protocol::response::Response,
+};
+use log::warn;
+use std::{collections::HashMap, sync::Arc};
+use tokio::{
+    spawn,
+    sync::{broadcast, mpsc, oneshot, RwLock},
+    task::{self, JoinHandle},
+    time::{sleep_until, Instant},
+};
+
+const COMMAND_BUFFER: usize = 32;
+
+pub enum GameCommand {
+    Join {
+        client_uuid: UserUUID,
+        role: Option<Role>,
+        reply: oneshot::Sender<Result<Role, &'static str>>,
+    },
+    Leave {
+        client_uuid: UserUUID,
+        reply: oneshot::Sender<Result<(), &'static str>>,
+    },
+    MakeMove {
+        client_uuid: UserUUID,
+        mv: Move,
+        reply: oneshot::Sender<Result<(), &'static str>>,
+    },
+    Disconnect {
+        client_uuid: UserUUID,
+    },
+    Reconnect {
+        client_uuid: UserUUID,
+        reply: oneshot::Sender<bool>,
+    },
+    HintPosition {
+        client_uuid: UserUUID,
+        reply: oneshot::Sender<Result<Board, &'static str>>,
+    },
+    CountHint {
+        client_uuid: UserUUID,
+    },
+    LivePosition {
+        client_uuid: UserUUID,
+        reply: oneshot::Sender<Option<Board>>,
+    },
+    Chat {
+        client_uuid: UserUUID,
+        text: String,
+        reply: oneshot::Sender<Result<(), &'static str>>,
+    },
+    Act {
+        client_uuid: UserUUID,
+        action: PlayerAction,
+        reply: oneshot::Sender<Result<(), &'static str>>,
+    },
+    GetState {
+        reply: oneshot::Sender<Response>,
+    },
+    PreviewMove {
+        mv: Move,
+        reply: oneshot::Sender<Result<Response, &'static str>>,
+    },
+    GetSummary {
+        reply: oneshot::Sender<GameSummary>,
+    },
+    GetRecord {
+        reply: oneshot::Sender<GameRecord>,
+    },
+    Expire {
+        reply: oneshot::Sender<()>,
+    },
+}
+
+// The only way to talk to a running game: commands go in through [commands] and are executed
+// one at a time by the game's task, events come out through [events].
+#[derive(Debug, Clone)]
+pub struct GameHandle {
+    commands: mpsc::Sender<GameCommand>,
+    events: broadcast::Sender<Response>,
+}
+pub type Games = Arc<RwLock<HashMap<GameUUID, GameHandle>>>;
+
+pub async fn find_game(games: &Games, game_uuid: &GameUUID) -> Option<GameHandle> {
+    games.read().await.get(game_uuid).cloned()
+}
+
+// Starts a new game's task, and lists the game in [games] and the lobby
+pub async fn launch_game(
+    game_uuid: GameUUID,
+    creator_uuid: UserUUID,
+    game_configuration: GameConfiguration,
+    state: &ServerState,
+) -> GameHandle {
+    let game = Game::new(game_uuid.clone(), creator_uuid, game_configuration, state);
+    let summary = game.summary();
+    let handle = GameHandle::spawn(game);
+    state.games.write().await.insert(game_uuid, handle.clone());
+    state.lobby.game_added(summary);
+    handle
+}
+
+// Starts a game with both seats already taken, so that nobody else can sit down in between.
+// Nothing is published if a player cannot be seated.
+pub async fn launch_seated_game(
+    game_uuid: GameUUID,
+    (first, second): (UserUUID, UserUUID),
+    game_configuration: GameConfiguration,
+    state: &ServerState,
+) -> Result<GameHandle, &'static str> {
+    let mut game = Game::new(game_uuid.clone(), first.clone(), game_configuration, state);
+    game.seat_players(first, second).await?;
+    let summary = game.summary();
+    let handle = GameHandle::spawn(game);
+    state.games.write().await.insert(game_uuid, handle.clone());
+    state.lobby.game_added(summary);
+    Ok(handle)
+}
+
+impl GameHandle {
+    pub fn spawn(game: Game) -> GameHandle {
+        let (commands, commands_rcv) = mpsc::channel(COMMAND_BUFFER);
+        let handle = GameHandle {
+            commands,
+            events: game.events(),
+        };
+        spawn(run(game, commands_rcv));
+        handle
+    }
+
+    // Subscribe before sending a command, so that none of the events it causes are missed
+    pub fn subscribe(&self) -> broadcast::Receiver<Response> {
+        self.events.subscribe()
+    }
+
+    // Without a [role], the client takes a free seat or else watches
+    pub async fn join(
+        &self,
+        client_uuid: UserUUID,
+        role: Option<Role>,
+    ) -> Result<Role, &'static str> {
+        self.request(|reply| GameCommand::Join {
+            client_uuid,
+            role,
+            reply,
+        })
+        .await?
+    }
+
+    pub async fn leave(&self, client_uuid: UserUUID) -> Result<(), &'static str> {
+        self.request(|reply| GameCommand::Leave { client_uuid, reply })
+            .await?
+    }
+
+    pub async fn make_move(&self, client_uuid: UserUUID, mv: Move) -> Result<(), &'static str> {
+        self.request(|reply| GameCommand::MakeMove {
+            client_uuid,
+            mv,
+            reply,
+        })
+        .await?
+    }
+
+    pub async fn disconnect(&self, client_uuid: UserUUID) {
+        let _ = self
+            .commands
+            .send(GameCommand::Disconnect { client_uuid })
+            .await;
+    }
+
+    // Returns whether the client takes part in the game
+    pub async fn reconnect(&self, client_uuid: UserUUID) -> Result<bool, &'static str> {
+        self.request(|reply| GameCommand::Reconnect { client_uuid, reply })
+            .await
+    }
+
+    // The position to search for a hint, if the player may have one
+    pub async fn hint_position(&self, client_uuid: UserUUID) -> Result<Board, &'static str> {
+        self.request(|reply| GameCommand::HintPosition { client_uuid, reply })
+            .await?
+    }
+
+    // Once the hint has been found
+    pub async fn count_hint(&self, client_uuid: UserUUID) {
+        let _ = self
+            .commands
+            .send(GameCommand::CountHint { client_uuid })
+            .await;
+    }
+
+    pub async fn send_chat(&self, client_uuid: UserUUID, text: String) -> Result<(), &'static str> {
+        self.request(|reply| GameCommand::Chat {
+            client_uuid,
+            text,
+            reply,
+        })
+        .await?
+    }
+
+    pub async fn act(
+        &self,
+        client_uuid: UserUUID,
+        action: PlayerAction,
+    ) -> Result<(), &'static str> {
+        self.request(|reply| GameCommand::Act {
+            client_uuid,
+            action,
+            reply,
+        })
+        .await?
+    }
+
+    pub async fn game_state(&self) -> Result<Response, &'static str> {
+        self.request(|reply| GameCommand::GetState { reply }).await
+    }
+
+    pub async fn preview_move(&self, mv: Move) -> Result<Response, &'static str> {
+        self.request(|reply| GameCommand::PreviewMove { mv, reply })
+            .await?
+    }
+
+    // The position, if [client_uuid] is seated and the game is not over yet
+    pub async fn live_position(
+        &self,
+        client_uuid: UserUUID,
+    ) -> Result<Option<Board>, &'static str> {
+        self.request(|reply| GameCommand::LivePosition { client_uuid, reply })
+            .await
+    }
+
+    pub async fn summary(&self) -> Result<GameSummary, &'static str> {
+        self.request(|reply| GameCommand::GetSummary { reply })
+            .await
+    }
+
+    pub async fn record(&self) -> Result<GameRecord, &'static str> {
+        self.request(|reply| GameCommand::GetRecord { reply }).await
+    }
+
+    pub async fn expire(&self) -> Result<(), &'static str> {
+        self.request(|reply| GameCommand::Expire { reply }).await
+    }
+
+    async fn request<T>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<T>) -> GameCommand,
+    ) -> Result<T, &'static str> {
+        let (reply, reply_rcv) = oneshot::channel();
+        self.commands
+            .send(command(reply))
+            .await
+            .map_err(|_| "The game is no longer running")?;
+        reply_rcv.await.map_err(|_| "The game is no longer running")
+    }
+}
+
+async fn execute(game: &mut Game, command: GameCommand) {
+    // The receiver may have given up waiting, in which case the reply is dropped
+    match command {
+        GameCommand::Join {
+            client_uuid,
+            role,
+            reply,
+        } => {
+            let _ = reply.send(game.add_client(client_uuid, role).await);
+        }
+        GameCommand::Leave { client_uuid, reply } => {
+            let _ = reply.send(game.remove_client(&client_uuid).await);
+        }
+        GameCommand::MakeMove {
+            client_uuid,
+            mv,
+            reply,
+        } => {
+            let _ = reply.send(game.make_move(&client_uuid, mv));
+        }
+        GameCommand::Disconnect { client_uuid } => {
+            game.disconnect(&client_uuid).await;
+        }
+        GameCommand::Reconnect { client_uuid, reply } => {
+            let _ = reply.send(game.reconnect(&client_uuid).await);
+        }
+        GameCommand::HintPosition { client_uuid, reply } => {
+            let _ = reply.send(game.hint_position(&client_uuid));
+        }
+        GameCommand::CountHint { client_uuid } => {
+            game.count_hint(&client_uuid);
+        }
+        GameCommand::LivePosition { client_uuid, reply } => {
+            let _ = reply.send(game.live_position(&client_uuid));
+        }
+        GameCommand::Chat {
+            client_uuid,
+            text,
+            reply,
+        } => {
+            let _ = reply.send(game.send_chat(&client_uuid, text).await);
+        }
+        GameCommand::Act {
+            client_uuid,
+            action,
+            reply,
+        } => {
+            let _ = reply.send(game.act(&client_uuid, action));
+        }
+        GameCommand::GetState { reply } => {
+            let _ = reply.send(game.game_state());
+        }
+        GameCommand::PreviewMove { mv, reply } => {
+            let _ = reply.send(game.preview_move(mv));
+        }
+        GameCommand::GetSummary { reply } => {
+            let _ = reply.send(game.summary());
+        }
+        GameCommand::GetRecord { reply } => {
+            let _ = reply.send(game.record());
+        }
+        GameCommand::Expire { reply } => {
+            game.expire();
+            let _ = reply.send(());
+        }
+    }
+}
+
+// The search is CPU-bound, so it runs on the blocking pool while the game keeps serving commands
+fn start_ai_search(game: &Game) -> Option<(Board, JoinHandle<Option<Move>>)> {
+    let side = game.ai_to_move()?;
+    let board = game.get_board().clone();
+    let ai = AI {
+        side,
+        board: board.clone(),
+        fuel: DEFAULT_FUEL,
+        evaluation: *game.evaluation(),
+        opening_book: Some(Arc::clone(game.opening_book())),
+    };
+    Some((board, task::spawn_blocking(move || ai.get_move())))
+}
+
+async fn run(mut game: Game, mut commands: mpsc::Receiver<GameCommand>) {
+    let mut ai_search: Option<(Board, JoinHandle<Option<Move>>)> = None;
+    let mut searched_board: Option<Board> = None;
+
+    loop {
+        // Search each position once, so that a position without an AI move is not retried forever
+        if ai_search.is_none() && searched_board.as_ref() != Some(game.get_board()) {
+            ai_search = start_ai_search(&game);
+            searched_board = ai_search.as_ref().map(|(board, _)| board.clone());
+        }
+
+        let deadline = game.deadline();
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(command) => execute(&mut game, command).await,
+                None => break, // Every handle is dropped
+            },
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                game.check_deadlines();
+            }
+            result = async { (&mut ai_search.as_mut().unwrap().1).await }, if ai_search.is_some() => {
+                let (board, _) = ai_search.take().unwrap();
+                match result {
+                    // Discard the move if the position changed while the AI was thinking
+                    Ok(Some(mv)) if *game.get_board() == board => {
+                        if let Err(e) = game.apply_ai_move(mv) {
+                            warn!("AI move {} rejected: {}", mv, e);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("AI search failed: {}", e),
+                }
+            }
+        }
+        game.rate().await;
+        game.annotate();
+    }
+
+    if let Some((_, search)) = ai_search {
+        search.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::player_side::PlayerSide;
+    use crate::state::{
+        clock::ClockState,
+        game_configuration::{ColorPreference, PlayerType, TimeControl, TimeControlCategory},
+        game_meta::{GameResult, GameState, Termination},
+        server_config::ServerConfig,
+    };
+    use futures::future::join_all;
+    use rand::Rng;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    fn new_user() -> UserUUID {
+        UserUUID::new(uuid::Uuid::new_v4().to_string()).unwrap()
+    }
+
+    fn configuration(time_control: Option<TimeControl>) -> GameConfiguration {
+        GameConfiguration {
+            creator_name: "Load".to_owned(),
+            opponent: PlayerType::Human,
+            side_selection: ColorPreference::AlwaysWhite,
+            time_control,
+            rated: false,
+            private: false,
+        }
+    }
+
+    fn new_game(state: &ServerState, configuration: GameConfiguration) -> GameHandle {
+        let game_uuid = uuid::Uuid::new_v4().simple().to_string();
+        GameHandle::spawn(Game::new(game_uuid, new_user(), configuration, state))
+    }
+
+    // Plays random moves, keeping a local copy of the board to choose them from
+    async fn play_random_moves(
+        game: GameHandle,
+        (white, black): (UserUUID, UserUUID),
+        n: usize,
+    ) -> usize {
+        let mut board = Board::new();
+        let mut moves_made = 0;
+        while moves_made < n && !board.is_game_over() {
+            let moves = board.get_valid_moves();
+            let mv = moves[rand::thread_rng().gen_range(0..moves.len())];
+            let player = match board.get_turn() {
+                PlayerSide::White => white.clone(),
+                PlayerSide::Black => black.clone(),
+            };
+            game.make_move(player, mv).await.unwrap();
+            board.make_move(mv).unwrap();
+            moves_made += 1;
+        }
+        moves_made
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_games_progress_independently() {
+        let state = ServerState::new(ServerConfig::default());
+        let mut games = vec![];
+        for _ in 0..200 {
+            let game = new_game(&state, configuration(None));
+            let (white, black) = (new_user(), new_user());
+            game.join(white.clone(), None).await.unwrap();
+            game.join(black.clone(), None).await.unwrap();
+            assert_eq!(
+                game.summary().await.unwrap().1.status,
+                GameState::InProgress
+            );
+            games.push((game, (white, black)));
+        }
+
+        let players = games
+            .into_iter()
+            .map(|(game, players)| spawn(play_random_moves(game, players, 20)));
+        let moves_made = timeout(Duration::from_secs(30), join_all(players))
+            .await
+            .expect("Games are blocked by each other");
+
+        // No game can end within 20 moves, so every player made all of them
+        for n in moves_made {
+            assert_eq!(n.unwrap(), 20);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn takeback_restores_both_clocks() {
+        let state = ServerState::new(ServerConfig::default());
+        let game = new_game(
+            &state,
+            configuration(Some(TimeControl {
+                time: Duration::from_secs(60),
+                increment: Duration::from_secs(10),
+            })),
+        );
+        let (white, black) = (new_user(), new_user());
+        game.join(white.clone(), None).await.unwrap();
+        game.join(black.clone(), None).await.unwrap();
+
+        let mut board = Board::new();
+        for (player, think) in [(&white, 5), (&black, 3), (&white, 2)] {
+            tokio::time::sleep(Duration::from_secs(think)).await;
+            let mv = board.get_valid_moves()[0];
+            game.make_move(player.clone(), mv).await.unwrap();
+            board.make_move(mv).unwrap();
+        }
+        game.act(white.clone(), PlayerAction::RequestTakeback)
+            .await
+            .unwrap();
+        game.act(black.clone(), PlayerAction::AcceptTakeback)
+            .await
+            .unwrap();
+
+        // White keeps the time spent on the undone move, but not the increment it earned
+        let Ok(Response::GameState { clock, .. }) = game.game_state().await else {
+            panic!("Expected the game state");
+        };
+        assert_eq!(
+            clock,
+            Some(ClockState {
+                white: Duration::from_secs(63),
+                black: Duration::from_secs(67),
+                running: Some(PlayerSide::White),
+            })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn flag_fall_ends_the_game() {
+        let state = ServerState::new(ServerConfig::default());
+        let game = new_game(
+            &state,
+            configuration(Some(TimeControl {
+                time: Duration::from_secs(60),
+                increment: Duration::from_secs(1),
+            })),
+        );
+        game.join(new_user(), None).await.unwrap();
+        game.join(new_user(), None).await.unwrap();
+
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        let (_, meta, _) = game.summary().await.unwrap();
+        assert_eq!(meta.status, GameState::Completed);
+        assert_eq!(
+            meta.result,
+            Some(GameResult {
+                winner: Some(PlayerSide::Black),
+                termination: Termination::Timeout,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn draw_by_agreement() {
+        let state = ServerState::new(ServerConfig::default());
+        let game = new_game(&state, configuration(None));
+        let (white, black) = (new_user(), new_user());
+        game.join(white.clone(), None).await.unwrap();
+        game.join(black.clone(), None).await.unwrap();
+
+        let mv = Board::new().get_valid_moves()[0];
+        game.make_move(white.clone(), mv).await.unwrap();
+        assert!(game.act(white.clone(), PlayerAction::Abort).await.is_err());
+
+        let mut events = game.subscribe();
+        game.act(white.clone(), PlayerAction::OfferDraw)
+            .await
+            .unwrap();
+        assert!(matches!(
+            events.recv().await,
+            Ok(Response::DrawOffered {
+                side: PlayerSide::White,
+                ..
+            })
+        ));
+        assert!(game
+            .act(white.clone(), PlayerAction::AcceptDraw)
+            .await
+            .is_err());
+        game.act(black.clone(), PlayerAction::AcceptDraw)
+            .await
+            .unwrap();
+
+        let (_, meta, _) = game.summary().await.unwrap();
+        assert_eq!(
+            meta.result,
+            Some(GameResult {
+                winner: None,
+                termination: Termination::Agreement,
+            })
+        );
+        assert!(game.act(black, PlayerAction::Resign).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn pending_games_refuse_moves() {
+        let state = ServerState::new(ServerConfig::default());
+        let game = new_game(&state, configuration(None));
+        let white = new_user();
+        game.join(white.clone(), None).await.unwrap();
+
+        let mv = Board::new().get_valid_moves()[0];
+        assert!(game.make_move(white.clone(), mv).await.is_err());
+        let (_, meta, _) = game.summary().await.unwrap();
+        assert_eq!(meta.status, GameState::Pending);
+
+        game.join(new_user(), None).await.unwrap();
+        game.make_move(white, mv).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn takeback_rewinds_the_last_turn() {
+        let state = ServerState::new(ServerConfig::default());
+        let game = new_game(&state, configuration(None));
+        let (white, black) = (new_user(), new_user());
+        game.join(white.clone(), None).await.unwrap();
+        game.join(black.clone(), None).await.unwrap();
+
+        let mut board = Board::new();
+        for player in [&white, &black, &white] {
+            let mv = board.get_valid_moves()[0];
+            game.make_move(player.clone(), mv).await.unwrap();
+            board.make_move(mv).unwrap();
+        }
+
+        assert!(game
+            .act(black.clone(), PlayerAction::AcceptTakeback)
+            .await
+            .is_err());
+        game.act(white.clone(), PlayerAction::RequestTakeback)
+            .await
+            .unwrap();
+        game.act(black.clone(), PlayerAction::AcceptTakeback)
+            .await
+            .unwrap();
+
+        let Ok(Response::GameState { game_state, .. }) = game.game_state().await else {
+            panic!("Expected the game state");
+        };
+        assert_eq!(game_state.nmove, 2);
+        assert_eq!(game_state.turn, PlayerSide::White);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn absent_player_abandons_the_game() {
+        let state = ServerState::new(ServerConfig::default());
+        let game = new_game(&state, configuration(None));
+        let (white, black) = (new_user(), new_user());
+        game.join(white.clone(), None).await.unwrap();
+        game.join(black.clone(), None).await.unwrap();
+
+        // Coming back within the grace period keeps the seat
+        let grace = ServerConfig::default().reconnect_grace;
+        game.disconnect(white.clone()).await;
+        tokio::time::sleep(grace / 2).await;
+        assert!(game.reconnect(white.clone()).await.unwrap());
+        tokio::time::sleep(grace).await;
+        assert_eq!(
+            game.summary().await.unwrap().1.status,
+            GameState::InProgress
+        );
+
+        // Sleep past the deadline, so that the game's timer fires first
+        game.disconnect(white.clone()).await;
+        tokio::time::sleep(grace + Duration::from_secs(1)).await;
+        let (_, meta, _) = game.summary().await.unwrap();
+        assert_eq!(
+            meta.result,
+            Some(GameResult {
+                winner: Some(PlayerSide::Black),
+                termination: Termination::Abandoned,
+            })
+        );
+        assert!(!game.reconnect(white).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn spectators_and_leaving() {
+        let state = ServerState::new(ServerConfig {
+            max_spectators: 1,
+            ..ServerConfig::default()
+        });
+        let game = new_game(&state, configuration(None));
+        let mut events = game.subscribe();
+        let (white, black, spectator) = (new_user(), new_user(), new_user());
+
+        assert_eq!(
+            game.join(spectator.clone(), Some(Role::Spectator)).await,
+            Ok(Role::Spectator)
+        );
+        assert!(game.join(new_user(), Some(Role::Spectator)).await.is_err());
+        assert_eq!(game.join(white.clone(), None).await, Ok(Role::Player));
+
+        // A player may give up the seat before the game starts
+        game.leave(white.clone()).await.unwrap();
+        assert_eq!(game.join(white.clone(), None).await, Ok(Role::Player));
+        assert_eq!(game.join(black.clone(), None).await, Ok(Role::Player));
+        assert!(game.join(new_user(), Some(Role::Player)).await.is_err());
+        assert!(game.leave(black.clone()).await.is_err());
+
+        game.leave(spectator.clone()).await.unwrap();
+        assert!(game.leave(spectator).await.is_err());
+        let mut last_count = None;
+        while let Ok(res) = events.try_recv() {
+            if let Response::GameParticipants { spectators, .. } = res {
+                last_count = Some(spectators);
+            }
+        }
+        assert_eq!(last_count, Some(0));
+    }
+
+    #[tokio::test]
+    async fn rated_game_updates_ratings() {
+        let state = ServerState::new(ServerConfig::default());
+        let game = new_game(
+            &state,
+            GameConfiguration {
+                rated: true,
+                ..configuration(None)
+            },
+        );
+        let (white, black) = (new_user(), new_user());
+        game.join(white.clone(), None).await.unwrap();
+        game.join(black.clone(), None).await.unwrap();
+
+        let mut events = game.subscribe();
+        game.act(white.clone(), PlayerAction::Resign).await.unwrap();
+        game.summary().await.unwrap(); // The rating happens after the command
+
+        let ratings = state.ratings.read().await;
+        let pool = TimeControlCategory::Unlimited;
+        assert!(ratings.get(&white, pool).rating < 1500.0);
+        assert!(ratings.get(&black, pool).rating > 1500.0);
+        let mut changes = None;
+        while let Ok(res) = events.try_recv() {
+            if let Response::GameParticipants {
+                rating_white: Some(rating),
+                ..
+            } = res
+            {
+                changes = rating.change;
+            }
+        }
+        assert!(changes.is_some_and(|change| change < 0.0));
+    }
+
+    #[tokio::test]
+    async fn bots_take_casual_seats_only() {
+        let state = ServerState::new(ServerConfig::default());
+        let (bot, token) = state.bots.write().await.register("bot".to_owned());
+        let rated = new_game(
+            &state,
+            GameConfiguration {
+                rated: true,
+                ..configuration(None)
+            },
+        );
+        // Whether or not the bot is connected
+        assert!(rated.join(bot.clone(), Some(Role::Player)).await.is_err());
+        let client = state
+            .bots
+            .read()
+            .await
+            .authenticate(&bot, Some(&token))
+            .unwrap()
+            .client();
+        state.clients.lock().await.insert(bot.clone(), client);
+        assert!(rated.join(bot.clone(), Some(Role::Player)).await.is_err());
+
+        let casual = new_game(&state, configuration(None));
+        let human = new_user();
+        casual.join(bot.clone(), None).await.unwrap();
+        casual.join(human.clone(), None).await.unwrap();
+        let mv = Board::new().get_valid_moves()[0];
+        assert!(casual.make_move(human, mv).await.is_err());
+        casual.make_move(bot.clone(), mv).await.unwrap();
+        let record = casual.record().await.unwrap();
+        let (_, player) = record.player_white.unwrap();
+        assert_eq!(*player.player_type(), PlayerType::Bot);
+    }
+
+    #[tokio::test]
+    async fn completed_game_is_annotated() {
+        let state = ServerState::new(ServerConfig {
+            annotation_depth: 1,
+            ..ServerConfig::default()
+        });
+        let game = new_game(&state, configuration(None));
+        let (white, black) = (new_user(), new_user());
+        game.join(white.clone(), None).await.unwrap();
+        game.join(black.clone(), None).await.unwrap();
+
+        let mut board = Board::new();
+        for player in [&white, &black, &white] {
+            let mv = board.get_valid_moves()[0];
+            game.make_move(player.clone(), mv).await.unwrap();
+            board.make_move(mv).unwrap();
+        }
+        let game_uuid = game.record().await.unwrap().game_uuid;
+        assert!(state.analysis.annotation(&game_uuid).await.is_none());
+
+        game.act(black, PlayerAction::Resign).await.unwrap();
+        let annotation = timeout(Duration::from_secs(30), async {
+            loop {
+                if let Some(annotation) = state.analysis.annotation(&game_uuid).await {
+                    return annotation;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("The game was not annotated");
+        assert_eq!(annotation.moves.len(), 3);
+        assert_eq!(annotation.moves[1].side, PlayerSide::Black);
+    }
+
+    #[tokio::test]
+    async fn hints_are_counted_in_casual_games() {
+        let state = ServerState::new(ServerConfig::default());
+        let (white, black) = (new_user(), new_user());
+        let casual = new_game(&state, configuration(None));
+        let rated = new_game(
+            &state,
+            GameConfiguration {
+                rated: true,
+                ..configuration(None)
+            },
+        );
+        for game in [&casual, &rated] {
+            game.join(white.clone(), None).await.unwrap();
+            game.join(black.clone(), None).await.unwrap();
+        }
+
+        assert_eq!(casual.hint_position(white.clone()).await, Ok(Board::new()));
+        assert!(casual.hint_position(black.clone()).await.is_err());
+        assert!(rated.hint_position(white.clone()).await.is_err());
+        // Asking for the position alone does not count; the hint counts once it is found
+        assert_eq!(casual.summary().await.unwrap().1.hints, (0, 0));
+        casual.count_hint(white.clone()).await;
+        assert_eq!(casual.summary().await.unwrap().1.hints, (1, 0));
+        assert_eq!(rated.summary().await.unwrap().1.hints, (0, 0));
+    }
+
+    #[tokio::test]
+    async fn preview_leaves_the_game_alone() {
+        let state = ServerState::new(ServerConfig::default());
+        let game = new_game(&state, configuration(None));
+        let (white, black) = (new_user(), new_user());
+        game.join(white.clone(), None).await.unwrap();
+        game.join(black.clone(), None).await.unwrap();
+        let moves = [
+            Move::new_wrc((0, 0), (0, 0, 0)),
+            Move::new_brc((0, 0), (3, 3, 0)),
+            Move::new_wrc((1, 0), (1, 0, 0)),
+            Move::new_brc((1, 0), (3, 2, 0)),
+            Move::new_wrc((2, 0), (0, 1, 0)),
+            Move::new_brc((2, 0), (3, 0, 0)),
+        ];
+        for (i, mv) in moves.into_iter().enumerate() {
+            let player = if i % 2 == 0 { &white } else { &black };
+            game.make_move(player.clone(), mv).await.unwrap();
+        }
+
+        let square = Move::new_wrc((3, 0), (1, 1, 0));
+        let Ok(Response::MovePreview {
+            position,
+            starts_take_back,
+            legal_moves,
+            ..
+        }) = game.preview_move(square).await
+        else {
+            panic!("Expected a preview");
+        };
+        assert!(starts_take_back);
+        assert_eq!(position.turn, PlayerSide::White);
+        assert!(legal_moves
+            .iter()
+            .all(|mv| mv.from.index.z == 0 && mv.to.index.z == 0));
+        assert!(game
+            .preview_move(Move::new_brc((3, 0), (2, 2, 0)))
+            .await
+            .is_err());
+
+        let Ok(Response::GameState {
+            game_state,
+            legal_moves,
+            ..
+        }) = game.game_state().await
+        else {
+            panic!("Expected the game state");
+        };
+        assert_eq!(game_state.nmove, 6);
+        assert_eq!(
+            legal_moves,
+            Board::replay(&moves).unwrap().get_valid_moves()
+        );
+    }
+}
@@ -1,17 +1,39 @@
+use crate::logic::player_side::PlayerSide;
 use chrono::{DateTime, Utc};
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
 pub enum GameState {
     Pending = 0,
     InProgress = 1,
     Completed = 2,
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
+pub enum Termination {
+    Apex = 0,        // A ball was placed on top of the pyramid
+    Timeout = 1,     // A player ran out of time
+    Resignation = 2, // A player resigned
+    Agreement = 3,   // The players agreed to a draw
+    Aborted = 4,     // The game was called off before the first move
+    Abandoned = 5,   // A player did not reconnect in time
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
+pub struct GameResult {
+    pub winner: Option<PlayerSide>, // None for draws and aborted games
+    pub termination: Termination,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug)]
 pub struct GameMeta {
     pub status: GameState,
     pub created_at: DateTime<Utc>,
     pub last_move_at: Option<DateTime<Utc>>,
+    pub result: Option<GameResult>, // Set once the game is completed
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Utc>>, // Likewise
+    #[serde(default)]
+    pub hints: (usize, usize), // White's and black's, only ever non-zero in casual games
 }
 
 impl GameMeta {
@@ -20,6 +42,9 @@ impl GameMeta {
             status: GameState::Pending,
             created_at: Utc::now(),
             last_move_at: None,
+            result: None,
+            completed_at: None,
+            hints: (0, 0),
         }
     }
 
@@ -27,11 +52,20 @@ impl GameMeta {
         self.last_move_at = Some(Utc::now());
     }
 
+    pub fn count_hint(&mut self, side: PlayerSide) {
+        match side {
+            PlayerSide::White => self.hints.0 += 1,
+            PlayerSide::Black => self.hints.1 += 1,
+        }
+    }
+
     pub fn promote_to_in_progress(&mut self) {
         self.status = GameState::InProgress;
     }
 
-    pub fn promote_to_completed(&mut self) {
+    pub fn promote_to_completed(&mut self, result: GameResult) {
         self.status = GameState::Completed;
+        self.result = Some(result);
+        self.completed_at = Some(Utc::now());
     }
 }
@@ -0,0 +1,200 @@
+use super::{
+    game_handle::GameHandle,
+    game_meta::{GameMeta, GameState},
+    game_uuid::GameUUID,
+    server_state::ServerState,
+};
+use chrono::{Duration, Utc};
+use log::info;
+use tokio::time::interval;
+
+// What a single sweep reclaimed
+#[derive(Debug, Default, PartialEq)]
+pub struct SweepReport {
+    pub expired_pending: usize,
+    pub expired_idle: usize,
+    pub archived: usize,
+    pub remaining: usize,
+}
+
+// A game is last active when it is completed, or else when its last move was made. A TTL too
+// large to add to a date never runs out.
+fn is_older_than(meta: &GameMeta, ttl: std::time::Duration) -> bool {
+    let last_activity = meta
+        .completed_at
+        .or(meta.last_move_at)
+        .unwrap_or(meta.created_at);
+    Duration::from_std(ttl)
+        .ok()
+        .and_then(|ttl| last_activity.checked_add_signed(ttl))
+        .is_some_and(|expiry| expiry <= Utc::now())
+}
+
+// Ends stale games and moves completed ones to the archive. A game in progress that is ended
+// here is archived by one of the next sweeps, once [completed_ttl] has passed.
+pub async fn sweep(state: &ServerState) -> SweepReport {
+    let (games, config) = (&state.games, &state.config);
+    let games_snapshot: Vec<(GameUUID, GameHandle)> = games
+        .read()
+        .await
+        .iter()
+        .map(|(game_uuid, game)| (game_uuid.clone(), game.clone()))
+        .collect();
+
+    let mut report = SweepReport::default();
+    let mut removed = vec![];
+    for (game_uuid, game) in games_snapshot {
+        let Ok((_, meta, _)) = game.summary().await else {
+            removed.push(game_uuid); // The game's task is gone
+            continue;
+        };
+
+        match meta.status {
+            GameState::Pending if is_older_than(&meta, config.pending_ttl) => {
+                let _ = game.expire().await;
+                removed.push(game_uuid);
+                report.expired_pending += 1;
+            }
+            GameState::InProgress if is_older_than(&meta, config.idle_ttl) => {
+                let _ = game.expire().await;
+                report.expired_idle += 1;
+            }
+            GameState::Completed if is_older_than(&meta, config.completed_ttl) => {
+                if let Ok(record) = game.record().await {
+                    state.archive.write().await.insert(record);
+                }
+                removed.push(game_uuid);
+                report.archived += 1;
+            }
+            _ => {}
+        }
+    }
+
+    // Dropping the last handle stops the game's task
+    let mut games_guard = games.write().await;
+    removed.into_iter().for_each(|game_uuid| {
+        games_guard.remove(&game_uuid);
+        state.lobby.game_removed(game_uuid);
+    });
+    report.remaining = games_guard.len();
+    report
+}
+
+pub async fn run_janitor(state: ServerState) {
+    let mut ticks = interval(state.config.janitor_interval);
+    loop {
+        ticks.tick().await;
+        let report = sweep(&state).await;
+        info!(
+            "[janitor]: expired {} pending and {} idle games, archived {} completed games; {} games live, {} archived",
+            report.expired_pending,
+            report.expired_idle,
+            report.archived,
+            report.remaining,
+            state.archive.read().await.len(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::response::Response;
+    use crate::state::{
+        game_configuration::{ColorPreference, GameConfiguration, PlayerType},
+        game_handle::launch_game,
+        game_meta::{GameResult, Termination},
+        server_config::ServerConfig,
+        user_uuid::UserUUID,
+    };
+    use std::time::Duration;
+
+    fn new_user() -> UserUUID {
+        UserUUID::new(uuid::Uuid::new_v4().to_string()).unwrap()
+    }
+
+    async fn add_game(state: &ServerState, players: usize) -> GameHandle {
+        let configuration = GameConfiguration {
+            creator_name: "Janitor".to_owned(),
+            opponent: PlayerType::Human,
+            side_selection: ColorPreference::Random,
+            time_control: None,
+            rated: false,
+            private: false,
+        };
+        let game_uuid = uuid::Uuid::new_v4().simple().to_string();
+        let game = launch_game(game_uuid, new_user(), configuration, state).await;
+        for _ in 0..players {
+            game.join(new_user(), None).await.unwrap();
+        }
+        game
+    }
+
+    #[tokio::test]
+    async fn stale_games_are_reclaimed() {
+        let state = ServerState::new(ServerConfig::default());
+        let mut lobby_events = state.lobby.subscribe();
+
+        add_game(&state, 1).await;
+        add_game(&state, 2).await;
+
+        // Nothing is stale with the default settings
+        assert_eq!(sweep(&state).await.remaining, 2);
+
+        let state = ServerState {
+            config: ServerConfig {
+                pending_ttl: Duration::ZERO,
+                idle_ttl: Duration::ZERO,
+                completed_ttl: Duration::ZERO,
+                ..ServerConfig::default()
+            },
+            ..state
+        };
+        let report = sweep(&state).await;
+        assert_eq!(
+            report,
+            SweepReport {
+                expired_pending: 1,
+                expired_idle: 1,
+                archived: 0,
+                remaining: 1,
+            }
+        );
+
+        let report = sweep(&state).await;
+        assert_eq!(report.archived, 1);
+        assert_eq!(report.remaining, 0);
+        assert_eq!(state.archive.read().await.len(), 1);
+
+        let mut removed = 0;
+        while let Ok(res) = lobby_events.try_recv() {
+            if let Response::GameRemoved { .. } = res {
+                removed += 1;
+            }
+        }
+        assert_eq!(removed, 2);
+    }
+
+    #[test]
+    fn completed_games_age_from_their_completion() {
+        let mut meta = GameMeta::new_pending();
+        meta.created_at -= chrono::Duration::hours(2);
+        meta.promote_to_completed(GameResult {
+            winner: None,
+            termination: Termination::Aborted,
+        });
+
+        assert!(!is_older_than(&meta, Duration::from_secs(60 * 60)));
+        assert!(is_older_than(&meta, Duration::ZERO));
+    }
+
+    #[test]
+    fn huge_ttls_never_run_out() {
+        let meta = GameMeta::new_pending();
+        assert!(!is_older_than(&meta, Duration::from_secs(u64::MAX)));
+        assert!(!is_older_than(
+            &meta,
+            Duration::from_secs(i64::MAX as u64 / 1000)
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+use super::{
+    game_configuration::{GameConfiguration, PlayerType, TimeControlCategory},
+    game_meta::{GameMeta, GameState},
+    game_uuid::GameUUID,
+};
+use crate::protocol::response::Response;
+use tokio::sync::broadcast;
+
+const LOBBY_BUFFER: usize = 256;
+
+// How a game is listed in the lobby
+pub type GameSummary = (GameUUID, GameMeta, GameConfiguration);
+
+// Every field is optional; an empty filter matches every game
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, Default)]
+#[serde(default)]
+pub struct LobbyFilter {
+    pub status: Option<GameState>,
+    pub opponent: Option<PlayerType>,
+    pub time_control: Option<TimeControlCategory>,
+    pub has_free_seat: Option<bool>,
+}
+
+impl LobbyFilter {
+    pub fn matches(&self, (_, game_meta, game_configuration): &GameSummary) -> bool {
+        // A game waits for its second player until it is promoted to [InProgress]
+        let has_free_seat = game_meta.status == GameState::Pending;
+        accepts(&self.status, &game_meta.status)
+            && accepts(&self.opponent, &game_configuration.opponent)
+            && accepts(
+                &self.time_control,
+                &game_configuration.time_control_category(),
+            )
+            && accepts(&self.has_free_seat, &has_free_seat)
+    }
+}
+
+// An unset criterion accepts any value
+fn accepts<T: PartialEq>(wanted: &Option<T>, actual: &T) -> bool {
+    wanted.iter().all(|wanted| wanted == actual)
+}
+
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Copy, Debug, Default,
+)]
+pub enum LobbySort {
+    #[default]
+    Newest = 0,
+    Oldest = 1,
+    RecentlyActive = 2, // Games without moves are ordered by creation
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, Default)]
+#[serde(default)]
+pub struct LobbyQuery {
+    pub filter: LobbyFilter,
+    pub sort: LobbySort,
+    pub offset: usize,
+    pub limit: Option<usize>, // None for every game from [offset] on
+}
+
+impl LobbyQuery {
+    // Returns the requested page, and the number of games matching the filter
+    pub fn apply(&self, games: Vec<GameSummary>) -> (Vec<GameSummary>, usize) {
+        let mut games: Vec<GameSummary> = games
+            .into_iter()
+            .filter(|game| self.filter.matches(game))
+            .collect();
+        match self.sort {
+            LobbySort::Newest => {
+                games.sort_by_key(|(_, game_meta, _)| std::cmp::Reverse(game_meta.created_at))
+            }
+            LobbySort::Oldest => games.sort_by_key(|(_, game_meta, _)| game_meta.created_at),
+            LobbySort::RecentlyActive => games.sort_by_key(|(_, game_meta, _)| {
+                std::cmp::Reverse(game_meta.last_move_at.unwrap_or(game_meta.created_at))
+            }),
+        }
+
+        let total = games.len();
+        let page = games
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        (page, total)
+    }
+}
+
+// Broadcasts [GameAdded], [GameUpdated] and [GameRemoved] to the clients watching the lobby.
+// Games publish their own updates whenever their [GameMeta] or seats change.
+#[derive(Debug, Clone)]
+pub struct Lobby {
+    events: broadcast::Sender<Response>,
+}
+
+impl Default for Lobby {
+    fn default() -> Self {
+        Lobby::new()
+    }
+}
+
+impl Lobby {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(LOBBY_BUFFER);
+        Lobby { events }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Response> {
+        self.events.subscribe()
+    }
+
+    // Private games are never announced
+    pub fn game_added(&self, game: GameSummary) {
+        if !game.2.private {
+            self.publish(Response::GameAdded { game });
+        }
+    }
+
+    pub fn game_updated(&self, game: GameSummary) {
+        if !game.2.private {
+            self.publish(Response::GameUpdated { game });
+        }
+    }
+
+    pub fn game_removed(&self, game_uuid: GameUUID) {
+        self.publish(Response::GameRemoved { game_uuid });
+    }
+
+    fn publish(&self, res: Response) {
+        // Fails only if nobody watches the lobby, which is fine
+        let _ = self.events.send(res);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::game_configuration::{ColorPreference, TimeControl};
+    use chrono::{Duration, Utc};
+
+    fn summary(
+        game_uuid: &str,
+        status: GameState,
+        age_minutes: i64,
+        time_control: Option<TimeControl>,
+    ) -> GameSummary {
+        let mut game_meta = GameMeta::new_pending();
+        game_meta.status = status;
+        game_meta.created_at = Utc::now() - Duration::minutes(age_minutes);
+        (
+            game_uuid.to_owned(),
+            game_meta,
+            GameConfiguration {
+                creator_name: "Lobby".to_owned(),
+                opponent: PlayerType::Human,
+                side_selection: ColorPreference::Random,
+                time_control,
+                rated: false,
+                private: false,
+            },
+        )
+    }
+
+    fn uuids(games: &[GameSummary]) -> Vec<&str> {
+        games.iter().map(|(uuid, _, _)| uuid.as_str()).collect()
+    }
+
+    #[test]
+    fn filter_sort_and_paginate() {
+        let blitz = TimeControl {
+            time: std::time::Duration::from_secs(180),
+            increment: std::time::Duration::from_secs(2),
+        };
+        let games = vec![
+            summary("a", GameState::Pending, 30, None),
+            summary("b", GameState::InProgress, 20, None),
+            summary("c", GameState::Pending, 10, Some(blitz)),
+            summary("d", GameState::Pending, 40, None),
+        ];
+
+        let query = LobbyQuery {
+            filter: LobbyFilter {
+                has_free_seat: Some(true),
+                ..LobbyFilter::default()
+            },
+            offset: 1,
+            limit: Some(1),
+            ..LobbyQuery::default()
+        };
+        let (page, total) = query.apply(games.clone());
+        assert_eq!(total, 3);
+        assert_eq!(uuids(&page), ["a"]);
+
+        let query = LobbyQuery {
+            filter: LobbyFilter {
+                time_control: Some(TimeControlCategory::Unlimited),
+                ..LobbyFilter::default()
+            },
+            sort: LobbySort::Oldest,
+            ..LobbyQuery::default()
+        };
+        let (page, total) = query.apply(games);
+        assert_eq!(total, 3);
+        assert_eq!(uuids(&page), ["d", "a", "b"]);
+    }
+
+    #[test]
+    fn private_games_are_not_announced() {
+        let lobby = Lobby::new();
+        let mut events = lobby.subscribe();
+        let mut private = summary("a", GameState::InProgress, 0, None);
+        private.2.private = true;
+        lobby.game_added(private.clone());
+        lobby.game_updated(private);
+        lobby.game_added(summary("b", GameState::Pending, 0, None));
+
+        assert!(matches!(
+            events.try_recv(),
+            Ok(Response::GameAdded { game }) if game.0 == "b"
+        ));
+        assert!(events.try_recv().is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use super::{
+    client::send_to,
+    game_configuration::{ColorPreference, GameConfiguration, PlayerType, TimeControl},
+    game_handle::launch_seated_game,
+    server_state::ServerState,
+    user_uuid::UserUUID,
+};
+use crate::{logic::player_side::PlayerSide, protocol::response::Response};
+use log::{info, warn};
+use rand::Rng;
+use std::{collections::HashMap, slice, sync::Arc, time::Duration};
+use tokio::{
+    sync::Mutex,
+    time::{interval, Instant},
+};
+use uuid::Uuid;
+
+const MATCHMAKING_INTERVAL: Duration = Duration::from_secs(1);
+const BASE_RATING_RANGE: f64 = 100.0;
+const RATING_RANGE_GROWTH: f64 = 10.0; // Per second spent waiting
+
+// What a client asks of its opponent, on top of the time control
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, Default)]
+#[serde(default)]
+pub struct MatchRules {
+    pub side_selection: ColorPreference, // Two clients insisting on the same side never meet
+    pub rated: bool,                     // Rated and casual searches never meet
+}
+
+#[derive(Debug, Clone)]
+struct Seeker {
+    client_uuid: UserUUID,
+    time_control: Option<TimeControl>,
+    rules: MatchRules,
+    rating: Option<f64>, // None for casual searches and unrated clients, which anyone will do for
+    since: Instant,
+}
+
+impl Seeker {
+    // The rating difference the seeker accepts, which widens the longer it waits
+    fn rating_range(&self, now: Instant) -> f64 {
+        let waited = now.saturating_duration_since(self.since).as_secs_f64();
+        BASE_RATING_RANGE + RATING_RANGE_GROWTH * waited
+    }
+
+    fn is_compatible(&self, other: &Seeker, now: Instant) -> bool {
+        let sides_are_compatible = !matches!(
+            (self.rules.side_selection, other.rules.side_selection),
+            (ColorPreference::AlwaysWhite, ColorPreference::AlwaysWhite)
+                | (ColorPreference::AlwaysBlack, ColorPreference::AlwaysBlack)
+        );
+        let ratings_are_compatible = match (self.rating, other.rating) {
+            (Some(rating), Some(other_rating)) => {
+                let difference = (rating - other_rating).abs();
+                difference <= self.rating_range(now) && difference <= other.rating_range(now)
+            }
+            _ => true,
+        };
+        self.client_uuid != other.client_uuid
+            && self.time_control == other.time_control
+            && self.rules.rated == other.rules.rated
+            && sides_are_compatible
+            && ratings_are_compatible
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pairing {
+    pub white: UserUUID,
+    pub black: UserUUID,
+    pub time_control: Option<TimeControl>,
+    pub rated: bool,
+}
+
+// Clients waiting for an opponent, oldest first
+#[derive(Debug, Default)]
+pub struct Matchmaker {
+    queue: Vec<Seeker>,
+    colour_balance: HashMap<UserUUID, i32>, // Games as white minus games as black
+}
+pub type Matchmaking = Arc<Mutex<Matchmaker>>;
+
+impl Matchmaker {
+    // Replaces an earlier search by the same client
+    pub fn seek(
+        &mut self,
+        client_uuid: UserUUID,
+        time_control: Option<TimeControl>,
+        rules: MatchRules,
+        rating: Option<f64>,
+        now: Instant,
+    ) {
+        self.cancel(&client_uuid);
+        self.queue.push(Seeker {
+            client_uuid,
+            time_control,
+            rules,
+            rating,
+            since: now,
+        });
+    }
+
+    // Forgets everything about a client that went away
+    pub fn disconnect(&mut self, client_uuid: &UserUUID) {
+        self.cancel(client_uuid);
+        self.colour_balance.remove(client_uuid);
+    }
+
+    // Returns whether the client was waiting
+    pub fn cancel(&mut self, client_uuid: &UserUUID) -> bool {
+        let waiting = self.queue.len();
+        self.queue
+            .retain(|seeker| &seeker.client_uuid != client_uuid);
+        self.queue.len() < waiting
+    }
+
+    // Pairs every seeker with the longest-waiting compatible one, and removes them from the queue
+    pub fn pair(&mut self, now: Instant) -> Vec<Pairing> {
+        let mut pairings = vec![];
+        let mut i = 0;
+        while i < self.queue.len() {
+            let opponent = (i + 1..self.queue.len())
+                .find(|&j| self.queue[i].is_compatible(&self.queue[j], now));
+            match opponent {
+                Some(j) => {
+                    let b = self.queue.remove(j);
+                    let a = self.queue.remove(i);
+                    pairings.push(self.assign_sides(a, b));
+                }
+                None => i += 1,
+            }
+        }
+        pairings
+    }
+
+    // Side preferences come first; otherwise whoever played white more often gets black
+    fn assign_sides(&mut self, a: Seeker, b: Seeker) -> Pairing {
+        let balance = |seeker: &Seeker| *self.colour_balance.get(&seeker.client_uuid).unwrap_or(&0);
+        let a_is_white = match (a.rules.side_selection, b.rules.side_selection) {
+            (ColorPreference::AlwaysWhite, _) | (_, ColorPreference::AlwaysBlack) => true,
+            (ColorPreference::AlwaysBlack, _) | (_, ColorPreference::AlwaysWhite) => false,
+            _ if balance(&a) != balance(&b) => balance(&a) < balance(&b),
+            _ => rand::thread_rng().gen(),
+        };
+        let (white, black) = if a_is_white { (a, b) } else { (b, a) };
+
+        *self
+            .colour_balance
+            .entry(white.client_uuid.clone())
+            .or_default() += 1;
+        *self
+            .colour_balance
+            .entry(black.client_uuid.clone())
+            .or_default() -= 1;
+        Pairing {
+            white: white.client_uuid,
+            black: black.client_uuid,
+            time_control: white.time_control,
+            rated: white.rules.rated,
+        }
+    }
+}
+
+// Pairs the waiting clients, starts their games, and tells both players with [MatchFound]
+pub async fn match_waiting(state: &ServerState) {
+    let pairings = state.matchmaking.lock().await.pair(Instant::now());
+    for pairing in pairings {
+        let game_configuration = GameConfiguration {
+            creator_name: "Matchmaking".to_owned(),
+            opponent: PlayerType::Human,
+            side_selection: ColorPreference::AlwaysWhite, // The first player to join is white
+            time_control: pairing.time_control,
+            rated: pairing.rated,
+            private: false,
+        };
+        let game_uuid = Uuid::new_v4().simple().to_string();
+        let seated = launch_seated_game(
+            game_uuid.clone(),
+            (pairing.white.clone(), pairing.black.clone()),
+            game_configuration,
+            state,
+        )
+        .await;
+        if let Err(e) = seated {
+            warn!("[matchmaking]: could not start {}: {}", game_uuid, e);
+            continue;
+        }
+
+        for (client_uuid, side) in [
+            (pairing.white, PlayerSide::White),
+            (pairing.black, PlayerSide::Black),
+        ] {
+            let res = Response::MatchFound {
+                game_uuid: game_uuid.clone(),
+                side,
+            };
+            send_to(&state.clients, slice::from_ref(&client_uuid), &res).await;
+        }
+        info!("[matchmaking]: started {}", game_uuid);
+    }
+}
+
+// Searches are also matched right away; this lets waiting clients meet as their ranges widen
+pub async fn run_matchmaker(state: ServerState) {
+    let mut ticks = interval(MATCHMAKING_INTERVAL);
+    loop {
+        ticks.tick().await;
+        match_waiting(&state).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::game_meta::GameState;
+
+    fn new_user() -> UserUUID {
+        UserUUID::new(uuid::Uuid::new_v4().to_string()).unwrap()
+    }
+
+    fn blitz() -> Option<TimeControl> {
+        Some(TimeControl {
+            time: Duration::from_secs(180),
+            increment: Duration::from_secs(2),
+        })
+    }
+
+    #[test]
+    fn pairs_compatible_seekers() {
+        let start = Instant::now();
+        let mut matchmaker = Matchmaker::default();
+        let (a, b, c) = (new_user(), new_user(), new_user());
+        let white = MatchRules {
+            side_selection: ColorPreference::AlwaysWhite,
+            rated: false,
+        };
+
+        matchmaker.seek(a.clone(), None, white.clone(), None, start);
+        matchmaker.seek(b.clone(), blitz(), MatchRules::default(), None, start);
+        matchmaker.seek(c.clone(), None, white, None, start);
+        let rated = MatchRules {
+            rated: true,
+            ..MatchRules::default()
+        };
+        matchmaker.seek(new_user(), None, rated, None, start);
+        assert!(matchmaker.pair(start).is_empty());
+
+        let d = new_user();
+        matchmaker.seek(d.clone(), None, MatchRules::default(), None, start);
+        assert_eq!(
+            matchmaker.pair(start),
+            [Pairing {
+                white: a,
+                black: d,
+                time_control: None,
+                rated: false,
+            }]
+        );
+        assert!(matchmaker.cancel(&c));
+        assert!(!matchmaker.cancel(&c));
+    }
+
+    #[test]
+    fn rating_range_widens() {
+        let start = Instant::now();
+        let mut matchmaker = Matchmaker::default();
+        let (a, b) = (new_user(), new_user());
+        matchmaker.seek(a, None, MatchRules::default(), Some(1500.0), start);
+        matchmaker.seek(b, None, MatchRules::default(), Some(1800.0), start);
+
+        assert!(matchmaker.pair(start + Duration::from_secs(10)).is_empty());
+        assert_eq!(matchmaker.pair(start + Duration::from_secs(20)).len(), 1);
+    }
+
+    #[test]
+    fn colours_alternate() {
+        let start = Instant::now();
+        let mut matchmaker = Matchmaker::default();
+        let (a, b) = (new_user(), new_user());
+
+        let mut whites = vec![];
+        for _ in 0..4 {
+            matchmaker.seek(a.clone(), None, MatchRules::default(), None, start);
+            matchmaker.seek(b.clone(), None, MatchRules::default(), None, start);
+            let pairing = matchmaker.pair(start).pop().unwrap();
+            whites.push(pairing.white == a);
+        }
+        // Whoever is white in one game is black in the next, which evens out the balance
+        assert_ne!(whites[0], whites[1]);
+        assert_ne!(whites[2], whites[3]);
+
+        matchmaker.disconnect(&a);
+        matchmaker.disconnect(&b);
+        assert!(matchmaker.colour_balance.is_empty());
+    }
+
+    #[tokio::test]
+    async fn matched_games_start_with_both_seats_taken() {
+        let state = ServerState::new(crate::state::server_config::ServerConfig::default());
+        let (a, b) = (new_user(), new_user());
+        for client_uuid in [&a, &b] {
+            state.matchmaking.lock().await.seek(
+                client_uuid.clone(),
+                None,
+                MatchRules::default(),
+                None,
+                Instant::now(),
+            );
+        }
+        let mut lobby_events = state.lobby.subscribe();
+        match_waiting(&state).await;
+
+        let game = state.games.read().await.values().next().cloned().unwrap();
+        let Ok(Response::GameAdded { game: summary }) = lobby_events.try_recv() else {
+            panic!("Expected the game to be listed");
+        };
+        assert_eq!(summary.1.status, GameState::InProgress);
+        let intruder = game
+            .join(new_user(), Some(crate::state::game::Role::Player))
+            .await;
+        assert!(intruder.is_err());
+    }
+}
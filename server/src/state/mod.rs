@@ -1,6 +1,21 @@
+pub mod analysis_pool;
+pub mod archive;
+pub mod bot;
+pub mod challenge;
+pub mod chat;
 pub mod client;
+pub mod clock;
 pub mod game;
 pub mod game_configuration;
+pub mod game_handle;
 pub mod game_meta;
 pub mod game_uuid;
+pub mod janitor;
+pub mod lobby;
+pub mod matchmaking;
+pub mod rating;
+pub mod server_config;
+pub mod server_state;
+pub mod stats;
+pub mod tournament;
 pub mod user_uuid;
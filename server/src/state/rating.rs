@@ -0,0 +1,213 @@
+use super::{game_configuration::TimeControlCategory, user_uuid::UserUUID};
+use crate::logic::player_side::PlayerSide;
+use std::{collections::HashMap, f64::consts::PI, sync::Arc};
+use tokio::sync::RwLock;
+
+// Glicko-2, with every game treated as a rating period of its own
+// [http://www.glicko.net/glicko/glicko2.pdf]
+const GLICKO_SCALE: f64 = 173.7178;
+const TAU: f64 = 0.5; // Constrains how fast the volatility changes
+const CONVERGENCE: f64 = 0.000_001;
+const PROVISIONAL_DEVIATION: f64 = 110.0; // Ratings less certain than this are provisional
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub games: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Rating {
+            rating: 1500.0,
+            deviation: 350.0,
+            volatility: 0.06,
+            games: 0,
+        }
+    }
+}
+
+impl Rating {
+    pub fn is_provisional(&self) -> bool {
+        self.deviation > PROVISIONAL_DEVIATION
+    }
+
+    // The rating after a game against [opponent]; [score] is 1 for a win, 0.5 for a draw, 0 for a loss
+    fn after_game(&self, opponent: &Rating, score: f64) -> Rating {
+        let mu = (self.rating - 1500.0) / GLICKO_SCALE;
+        let phi = self.deviation / GLICKO_SCALE;
+        let opponent_mu = (opponent.rating - 1500.0) / GLICKO_SCALE;
+        let opponent_phi = opponent.deviation / GLICKO_SCALE;
+
+        let g = 1.0 / (1.0 + 3.0 * opponent_phi.powi(2) / PI.powi(2)).sqrt();
+        let expected = 1.0 / (1.0 + (-g * (mu - opponent_mu)).exp());
+        let variance = 1.0 / (g.powi(2) * expected * (1.0 - expected));
+        let delta = variance * g * (score - expected);
+
+        let volatility = self.next_volatility(phi, variance, delta);
+        let pre_period_phi = (phi.powi(2) + volatility.powi(2)).sqrt();
+        let new_phi = 1.0 / (1.0 / pre_period_phi.powi(2) + 1.0 / variance).sqrt();
+        let new_mu = mu + new_phi.powi(2) * g * (score - expected);
+
+        Rating {
+            rating: 1500.0 + GLICKO_SCALE * new_mu,
+            deviation: GLICKO_SCALE * new_phi,
+            volatility,
+            games: self.games + 1,
+        }
+    }
+
+    // Step 5 of the paper, using the Illinois algorithm
+    fn next_volatility(&self, phi: f64, variance: f64, delta: f64) -> f64 {
+        let a = (self.volatility.powi(2)).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta.powi(2) - phi.powi(2) - variance - ex)
+                / (2.0 * (phi.powi(2) + variance + ex).powi(2))
+                - (x - a) / TAU.powi(2)
+        };
+
+        let mut lower = a;
+        let mut upper = if delta.powi(2) > phi.powi(2) + variance {
+            (delta.powi(2) - phi.powi(2) - variance).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+        let (mut f_lower, mut f_upper) = (f(lower), f(upper));
+        while (upper - lower).abs() > CONVERGENCE {
+            let next = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_next = f(next);
+            if f_next * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = next;
+            f_upper = f_next;
+        }
+        (lower / 2.0).exp()
+    }
+}
+
+// A player's rating as shown next to the board
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
+pub struct PlayerRating {
+    pub rating: f64,
+    pub provisional: bool,
+    pub change: Option<f64>, // Set once a rated game is over
+}
+
+// Every user's rating, in a separate pool per time control category
+#[derive(Debug, Default)]
+pub struct RatingBook {
+    ratings: HashMap<UserUUID, HashMap<TimeControlCategory, Rating>>,
+}
+pub type Ratings = Arc<RwLock<RatingBook>>;
+
+impl RatingBook {
+    // Users start out with the default rating in every pool
+    pub fn get(&self, user_uuid: &UserUUID, pool: TimeControlCategory) -> Rating {
+        self.ratings
+            .get(user_uuid)
+            .and_then(|pools| pools.get(&pool))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // The pools the user has played rated games in
+    pub fn profile(&self, user_uuid: &UserUUID) -> HashMap<TimeControlCategory, Rating> {
+        self.ratings.get(user_uuid).cloned().unwrap_or_default()
+    }
+
+    // Everyone who has played in the pool
+    pub fn pool(&self, pool: TimeControlCategory) -> impl Iterator<Item = (&UserUUID, &Rating)> {
+        self.ratings
+            .iter()
+            .filter_map(move |(user_uuid, pools)| Some((user_uuid, pools.get(&pool)?)))
+    }
+
+    // Updates both players' ratings, and returns their changes (white, black)
+    pub fn record_game(
+        &mut self,
+        white: &UserUUID,
+        black: &UserUUID,
+        pool: TimeControlCategory,
+        winner: Option<PlayerSide>,
+    ) -> (f64, f64) {
+        let white_score = match winner {
+            Some(PlayerSide::White) => 1.0,
+            Some(PlayerSide::Black) => 0.0,
+            None => 0.5,
+        };
+        let (white_rating, black_rating) = (self.get(white, pool), self.get(black, pool));
+        let new_white = white_rating.after_game(&black_rating, white_score);
+        let new_black = black_rating.after_game(&white_rating, 1.0 - white_score);
+        let changes = (
+            new_white.rating - white_rating.rating,
+            new_black.rating - black_rating.rating,
+        );
+
+        for (user_uuid, rating) in [(white, new_white), (black, new_black)] {
+            self.ratings
+                .entry(user_uuid.clone())
+                .or_default()
+                .insert(pool, rating);
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The player from the paper's example, after the first of its three games
+    #[test]
+    fn glicko2_example() {
+        let player = Rating {
+            rating: 1500.0,
+            deviation: 200.0,
+            volatility: 0.06,
+            games: 0,
+        };
+        let opponent = Rating {
+            rating: 1400.0,
+            deviation: 30.0,
+            volatility: 0.06,
+            games: 0,
+        };
+        let after = player.after_game(&opponent, 1.0);
+        assert!((after.rating - 1563.6).abs() < 0.1, "{}", after.rating);
+        assert!((after.deviation - 175.4).abs() < 0.1, "{}", after.deviation);
+        assert!((after.volatility - 0.06).abs() < 0.0001);
+    }
+
+    #[test]
+    fn pools_are_separate() {
+        let mut book = RatingBook::default();
+        let white = UserUUID::new(uuid::Uuid::new_v4().to_string()).unwrap();
+        let black = UserUUID::new(uuid::Uuid::new_v4().to_string()).unwrap();
+
+        let (white_change, black_change) = book.record_game(
+            &white,
+            &black,
+            TimeControlCategory::Blitz,
+            Some(PlayerSide::White),
+        );
+        assert!(white_change > 0.0 && black_change < 0.0);
+        assert!((white_change + black_change).abs() < 0.001);
+        assert_eq!(book.get(&white, TimeControlCategory::Blitz).games, 1);
+        assert_eq!(
+            book.get(&white, TimeControlCategory::Rapid),
+            Rating::default()
+        );
+        assert_eq!(book.profile(&black).len(), 1);
+    }
+}
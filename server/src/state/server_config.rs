@@ -0,0 +1,120 @@
+use log::warn;
+use std::{env, path::PathBuf, str::FromStr, time::Duration};
+
+// Server-wide settings, read from the environment at startup
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub reconnect_grace: Duration, // How long a disconnected player keeps their seat
+    pub max_spectators: usize,     // How many spectators a single game admits
+
+    pub chat_max_length: usize, // How many characters a chat message may have
+    pub chat_messages_per_minute: usize, // How many chat messages a user may send per game
+    pub chat_history: usize,    // How many chat messages a game keeps
+
+    pub janitor_interval: Duration, // How often stale games are cleaned up
+    pub pending_ttl: Duration,      // How long a game waits for an opponent
+    pub idle_ttl: Duration,         // How long a game in progress may go without a move
+    pub completed_ttl: Duration,    // How long a completed game stays live before it is archived
+    pub archive_capacity: usize,    // How many archived games are kept
+    pub challenge_ttl: Duration,    // How long a challenge waits for an answer
+
+    pub bot_operator_token: Option<String>, // Needed to register bots; nobody can if None
+
+    pub analysis_workers: usize, // Threads reserved for position analysis
+    pub analysis_queue: usize,   // How many analyses may wait for a worker
+    pub annotation_depth: u32,   // The search depth completed games are annotated at
+
+    pub opening_book: Option<PathBuf>, // The computer plays without a book if None
+    pub evaluation: Option<PathBuf>,   // The computer uses the default weights if None
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            reconnect_grace: Duration::from_secs(120),
+            max_spectators: 100,
+
+            chat_max_length: 500,
+            chat_messages_per_minute: 10,
+            chat_history: 100,
+
+            janitor_interval: Duration::from_secs(60),
+            pending_ttl: Duration::from_secs(24 * 60 * 60),
+            idle_ttl: Duration::from_secs(24 * 60 * 60),
+            completed_ttl: Duration::from_secs(60 * 60),
+            archive_capacity: 10_000,
+            challenge_ttl: Duration::from_secs(5 * 60),
+
+            bot_operator_token: None,
+
+            analysis_workers: 2,
+            analysis_queue: 16,
+            annotation_depth: 3,
+
+            opening_book: None,
+            evaluation: None,
+        }
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            warn!("[server_config]: ignoring invalid {}={}", name, value);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+fn env_secs_or(name: &str, default: Duration) -> Duration {
+    Duration::from_secs(env_or(name, default.as_secs()))
+}
+
+// For periods of repeated work, which cannot be empty
+fn env_period_or(name: &str, default: Duration) -> Duration {
+    let period = env_secs_or(name, default);
+    if period.is_zero() {
+        warn!("[server_config]: ignoring {}=0, it must be positive", name);
+        return default;
+    }
+    period
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Self {
+        let default = ServerConfig::default();
+        ServerConfig {
+            reconnect_grace: env_secs_or("PYLOS_RECONNECT_GRACE_SECS", default.reconnect_grace),
+            max_spectators: env_or("PYLOS_MAX_SPECTATORS", default.max_spectators),
+
+            chat_max_length: env_or("PYLOS_CHAT_MAX_LENGTH", default.chat_max_length),
+            chat_messages_per_minute: env_or(
+                "PYLOS_CHAT_MESSAGES_PER_MINUTE",
+                default.chat_messages_per_minute,
+            ),
+            chat_history: env_or("PYLOS_CHAT_HISTORY", default.chat_history),
+
+            janitor_interval: env_period_or(
+                "PYLOS_JANITOR_INTERVAL_SECS",
+                default.janitor_interval,
+            ),
+            pending_ttl: env_secs_or("PYLOS_PENDING_TTL_SECS", default.pending_ttl),
+            idle_ttl: env_secs_or("PYLOS_IDLE_TTL_SECS", default.idle_ttl),
+            completed_ttl: env_secs_or("PYLOS_COMPLETED_TTL_SECS", default.completed_ttl),
+            archive_capacity: env_or("PYLOS_ARCHIVE_CAPACITY", default.archive_capacity),
+            challenge_ttl: env_secs_or("PYLOS_CHALLENGE_TTL_SECS", default.challenge_ttl),
+
+            bot_operator_token: env::var("PYLOS_BOT_OPERATOR_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+
+            analysis_workers: env_or("PYLOS_ANALYSIS_WORKERS", default.analysis_workers),
+            analysis_queue: env_or("PYLOS_ANALYSIS_QUEUE", default.analysis_queue),
+            annotation_depth: env_or("PYLOS_ANNOTATION_DEPTH", default.annotation_depth),
+
+            opening_book: env::var_os("PYLOS_OPENING_BOOK").map(PathBuf::from),
+            evaluation: env::var_os("PYLOS_EVALUATION").map(PathBuf::from),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use super::{
+    analysis_pool::AnalysisPool,
+    archive::{Archive, GameArchive},
+    bot::Bots,
+    challenge::Challenges,
+    client::Clients,
+    game_handle::Games,
+    lobby::Lobby,
+    matchmaking::{Matchmaker, Matchmaking},
+    rating::{RatingBook, Ratings},
+    server_config::ServerConfig,
+    tournament::Tournaments,
+};
+use crate::logic::{evaluation::Evaluation, opening_book::OpeningBook};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{Mutex, RwLock};
+
+// The registries shared by every connection
+#[derive(Debug, Clone)]
+pub struct ServerState {
+    pub clients: Clients,
+    pub bots: Bots,
+    pub games: Games,
+    pub archive: Archive,
+    pub lobby: Lobby,
+    pub matchmaking: Matchmaking,
+    pub ratings: Ratings,
+    pub tournaments: Tournaments,
+    pub challenges: Challenges,
+    pub opening_book: Arc<OpeningBook>,
+    pub evaluation: Evaluation,
+    pub analysis: AnalysisPool,
+    pub config: ServerConfig,
+}
+
+impl ServerState {
+    pub fn new(config: ServerConfig) -> Self {
+        let evaluation = Evaluation::load_or_default(config.evaluation.as_deref());
+        ServerState {
+            clients: Arc::new(Mutex::new(HashMap::new())),
+            bots: Bots::default(),
+            games: Arc::new(RwLock::new(HashMap::new())),
+            archive: Arc::new(RwLock::new(GameArchive::new(config.archive_capacity))),
+            lobby: Lobby::new(),
+            matchmaking: Arc::new(Mutex::new(Matchmaker::default())),
+            ratings: Arc::new(RwLock::new(RatingBook::default())),
+            tournaments: Arc::new(RwLock::new(HashMap::new())),
+            challenges: Challenges::default(),
+            opening_book: Arc::new(OpeningBook::load_or_empty(config.opening_book.as_deref())),
+            evaluation,
+            analysis: AnalysisPool::new(&config, evaluation),
+            config,
+        }
+    }
+}
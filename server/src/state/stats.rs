@@ -0,0 +1,267 @@
+use super::{
+    archive::GameRecord,
+    game::Player,
+    game_configuration::{PlayerType, TimeControlCategory},
+    game_meta::{GameState, Termination},
+    rating::RatingBook,
+    server_state::ServerState,
+    user_uuid::UserUUID,
+};
+use crate::logic::player_side::PlayerSide;
+use std::cmp::Reverse;
+
+pub const MAX_LEADERBOARD_SIZE: usize = 100;
+
+// Wins, draws and losses over some of a user's games
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub win_rate: Option<f64>, // None until a game has been played
+}
+
+impl Score {
+    fn add(&mut self, winner: Option<PlayerSide>, side: PlayerSide) {
+        match winner {
+            Some(winner) if winner == side => self.wins += 1,
+            Some(_) => self.losses += 1,
+            None => self.draws += 1,
+        }
+        let games = self.wins + self.draws + self.losses;
+        self.win_rate = Some(self.wins as f64 / games as f64);
+    }
+}
+
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct UserStats {
+    pub user_uuid: UserUUID,
+    pub games: u32,
+    pub as_white: Score,
+    pub as_black: Score,
+    pub against_computer: Score, // The computer has a single strength for now
+    pub average_moves: Option<f64>,
+}
+
+// The user's record over the completed games among [records]. Aborted games do not count.
+pub fn user_stats<'a>(
+    user_uuid: &UserUUID,
+    records: impl IntoIterator<Item = &'a GameRecord>,
+) -> UserStats {
+    let mut stats = UserStats {
+        user_uuid: user_uuid.clone(),
+        games: 0,
+        as_white: Score::default(),
+        as_black: Score::default(),
+        against_computer: Score::default(),
+        average_moves: None,
+    };
+    let mut total_moves = 0;
+
+    for record in records {
+        let Some(result) = &record.game_meta.result else {
+            continue;
+        };
+        if result.termination == Termination::Aborted {
+            continue;
+        }
+        let is_user = |seat: &Option<(UserUUID, Player)>| {
+            seat.as_ref().is_some_and(|(uuid, player)| {
+                uuid == user_uuid && *player.player_type() != PlayerType::Computer
+            })
+        };
+        let side = if is_user(&record.player_white) {
+            PlayerSide::White
+        } else if is_user(&record.player_black) {
+            PlayerSide::Black
+        } else {
+            continue;
+        };
+
+        match side {
+            PlayerSide::White => stats.as_white.add(result.winner, side),
+            PlayerSide::Black => stats.as_black.add(result.winner, side),
+        }
+        if record.game_configuration.opponent == PlayerType::Computer {
+            stats.against_computer.add(result.winner, side);
+        }
+        stats.games += 1;
+        total_moves += record.moves.len();
+    }
+
+    if stats.games > 0 {
+        stats.average_moves = Some(total_moves as f64 / stats.games as f64);
+    }
+    stats
+}
+
+// The archived games and the completed ones still in [Games]
+pub async fn completed_games(state: &ServerState) -> Vec<GameRecord> {
+    let mut records: Vec<GameRecord> = state.archive.read().await.records().cloned().collect();
+    let games: Vec<_> = state.games.read().await.values().cloned().collect();
+    for game in games {
+        if let Ok(record) = game.record().await {
+            if record.game_meta.status == GameState::Completed {
+                records.push(record);
+            }
+        }
+    }
+    records
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum LeaderboardSort {
+    #[default]
+    Rating,
+    Games,
+}
+
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct LeaderboardEntry {
+    pub user_uuid: UserUUID,
+    pub user_name: Option<String>, // Filled in for registered users
+    pub rating: f64,
+    pub provisional: bool,
+    pub games: u32,
+}
+
+// The top [limit] players of the pool. Players with established ratings rank above
+// provisional ones when sorting by rating.
+pub fn leaderboard(
+    ratings: &RatingBook,
+    pool: TimeControlCategory,
+    sort: LeaderboardSort,
+    limit: usize,
+) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = ratings
+        .pool(pool)
+        .map(|(user_uuid, rating)| LeaderboardEntry {
+            user_uuid: user_uuid.clone(),
+            user_name: None,
+            rating: rating.rating,
+            provisional: rating.is_provisional(),
+            games: rating.games,
+        })
+        .collect();
+
+    match sort {
+        LeaderboardSort::Rating => entries.sort_by(|a, b| {
+            a.provisional
+                .cmp(&b.provisional)
+                .then(b.rating.total_cmp(&a.rating))
+        }),
+        LeaderboardSort::Games => entries.sort_by_key(|entry| Reverse(entry.games)),
+    }
+    entries.truncate(limit.min(MAX_LEADERBOARD_SIZE));
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{
+        game_configuration::{ColorPreference, GameConfiguration},
+        game_meta::{GameMeta, GameResult},
+    };
+
+    fn new_user() -> UserUUID {
+        UserUUID::new(uuid::Uuid::new_v4().to_string()).unwrap()
+    }
+
+    fn record(
+        white: (&UserUUID, Player),
+        black: (&UserUUID, Player),
+        winner: Option<PlayerSide>,
+        termination: Termination,
+    ) -> GameRecord {
+        let mut game_meta = GameMeta::new_pending();
+        game_meta.promote_to_completed(GameResult {
+            winner,
+            termination,
+        });
+        let opponent = black.1.player_type().clone();
+        GameRecord {
+            game_uuid: uuid::Uuid::new_v4().simple().to_string(),
+            game_meta,
+            game_configuration: GameConfiguration {
+                creator_name: "Stats".to_owned(),
+                opponent,
+                side_selection: ColorPreference::AlwaysWhite,
+                time_control: None,
+                rated: false,
+                private: false,
+            },
+            player_white: Some((white.0.clone(), white.1)),
+            player_black: Some((black.0.clone(), black.1)),
+            moves: vec![],
+            chat: vec![],
+        }
+    }
+
+    #[test]
+    fn stats_by_colour_and_opponent() {
+        let (user, other) = (new_user(), new_user());
+        let human = Player::new_human;
+        let records = [
+            record(
+                (&user, human()),
+                (&other, human()),
+                Some(PlayerSide::White),
+                Termination::Apex,
+            ),
+            record(
+                (&other, human()),
+                (&user, human()),
+                None,
+                Termination::Agreement,
+            ),
+            record(
+                (&user, human()),
+                (&user, Player::new_computer()),
+                Some(PlayerSide::Black),
+                Termination::Resignation,
+            ),
+            record(
+                (&user, human()),
+                (&other, human()),
+                None,
+                Termination::Aborted,
+            ),
+        ];
+
+        let stats = user_stats(&user, &records);
+        assert_eq!(stats.games, 3);
+        assert_eq!((stats.as_white.wins, stats.as_white.losses), (1, 1));
+        assert_eq!(stats.as_black.draws, 1);
+        assert_eq!(stats.against_computer.win_rate, Some(0.0));
+        assert_eq!(stats.average_moves, Some(0.0));
+        assert_eq!(user_stats(&new_user(), &records).average_moves, None);
+    }
+
+    #[test]
+    fn provisional_ratings_rank_last() {
+        let mut book = RatingBook::default();
+        let (a, b, c) = (new_user(), new_user(), new_user());
+        let pool = TimeControlCategory::Blitz;
+        for _ in 0..20 {
+            book.record_game(&a, &b, pool, None);
+        }
+        book.record_game(&c, &a, pool, Some(PlayerSide::White));
+
+        let by_rating = leaderboard(&book, pool, LeaderboardSort::Rating, 10);
+        assert_eq!(by_rating.len(), 3);
+        assert!(!by_rating[0].provisional);
+        assert_eq!(by_rating[2].user_uuid, c);
+        assert_eq!(
+            leaderboard(&book, pool, LeaderboardSort::Games, 1)[0].user_uuid,
+            a
+        );
+        assert!(leaderboard(
+            &book,
+            TimeControlCategory::Rapid,
+            LeaderboardSort::Rating,
+            10
+        )
+        .is_empty());
+    }
+}
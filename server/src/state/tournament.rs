@@ -0,0 +1,809 @@
+use super::{
+    client::send_to,
+    game_configuration::{ColorPreference, GameConfiguration, PlayerType, TimeControl},
+    game_handle::{find_game, launch_seated_game},
+    game_meta::{GameResult, GameState, Termination},
+    game_uuid::GameUUID,
+    server_state::ServerState,
+    user_uuid::UserUUID,
+};
+use crate::{logic::player_side::PlayerSide, protocol::response::Response};
+use log::{info, warn};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::RwLock, time::interval};
+use uuid::Uuid;
+
+pub type TournamentUUID = String;
+
+const TOURNAMENT_INTERVAL: Duration = Duration::from_secs(2);
+pub const MAX_PARTICIPANTS: usize = 64;
+// How many partial pairings the Swiss search may try before it allows rematches
+const PAIRING_ATTEMPTS: usize = 10_000;
+
+type Pairs = Vec<(UserUUID, Option<UserUUID>)>; // White and black, or a bye
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
+pub enum TournamentFormat {
+    RoundRobin = 0, // Everyone plays everyone once
+    Swiss = 1,      // Players with similar scores meet, for a fixed number of rounds
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug)]
+pub struct TournamentConfiguration {
+    pub name: String,
+    pub format: TournamentFormat,
+    #[serde(default)]
+    pub rounds: Option<usize>, // Swiss only; enough rounds to find a winner if left out
+    pub time_control: Option<TimeControl>, // None if unlimited
+    #[serde(default)]
+    pub rated: bool,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
+pub enum TournamentStatus {
+    Registering = 0,
+    InProgress = 1,
+    Completed = 2,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
+pub struct TournamentPairing {
+    pub white: UserUUID,
+    pub black: Option<UserUUID>, // None for a bye, which counts as a win
+    pub game_uuid: Option<GameUUID>,
+    pub result: Option<GameResult>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub user_uuid: UserUUID,
+    pub points: f64,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub buchholz: f64,         // The sum of the opponents' points
+    pub sonneborn_berger: f64, // The points of the opponents beaten, plus half of those drawn
+}
+
+#[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug)]
+pub struct Tournament {
+    pub tournament_uuid: TournamentUUID,
+    pub creator_uuid: UserUUID,
+    pub configuration: TournamentConfiguration,
+    pub status: TournamentStatus,
+    pub participants: Vec<UserUUID>, // In order of registration, which seeds the first round
+    pub total_rounds: usize,         // Known once the tournament starts
+    pub rounds: Vec<Vec<TournamentPairing>>,
+}
+pub type Tournaments = Arc<RwLock<HashMap<TournamentUUID, Tournament>>>;
+
+// The points [side] scored in a finished game; aborted games are lost by both players
+fn points(result: &GameResult, side: PlayerSide) -> f64 {
+    match result.winner {
+        Some(winner) if winner == side => 1.0,
+        Some(_) => 0.0,
+        None if result.termination == Termination::Aborted => 0.0,
+        None => 0.5,
+    }
+}
+
+impl Tournament {
+    pub fn new(
+        tournament_uuid: TournamentUUID,
+        creator_uuid: UserUUID,
+        configuration: TournamentConfiguration,
+    ) -> Self {
+        Tournament {
+            tournament_uuid,
+            creator_uuid,
+            configuration,
+            status: TournamentStatus::Registering,
+            participants: vec![],
+            total_rounds: 0,
+            rounds: vec![],
+        }
+    }
+
+    pub fn register(&mut self, user_uuid: UserUUID) -> Result<(), &'static str> {
+        if self.status != TournamentStatus::Registering {
+            return Err("The tournament has already started");
+        }
+        if self.participants.contains(&user_uuid) {
+            return Err("You are already registered");
+        }
+        if self.participants.len() >= MAX_PARTICIPANTS {
+            return Err("The tournament is full");
+        }
+        self.participants.push(user_uuid);
+        Ok(())
+    }
+
+    pub fn withdraw(&mut self, user_uuid: &UserUUID) -> Result<(), &'static str> {
+        if self.status != TournamentStatus::Registering {
+            return Err("The tournament has already started");
+        }
+        let registered = self.participants.len();
+        self.participants
+            .retain(|participant| participant != user_uuid);
+        if self.participants.len() == registered {
+            return Err("You are not registered");
+        }
+        Ok(())
+    }
+
+    // Closes the registration; the first round is paired by [next_round]
+    pub fn start(&mut self, user_uuid: &UserUUID) -> Result<(), &'static str> {
+        if *user_uuid != self.creator_uuid {
+            return Err("Only the creator can start the tournament");
+        }
+        if self.status != TournamentStatus::Registering {
+            return Err("The tournament has already started");
+        }
+        if self.participants.len() < 2 {
+            return Err("A tournament needs at least two participants");
+        }
+
+        let n = self.participants.len();
+        self.total_rounds = match self.configuration.format {
+            TournamentFormat::RoundRobin => n + n % 2 - 1,
+            TournamentFormat::Swiss => {
+                let enough = n.next_power_of_two().trailing_zeros() as usize;
+                self.configuration.rounds.unwrap_or(enough).clamp(1, n - 1)
+            }
+        };
+        self.status = TournamentStatus::InProgress;
+        Ok(())
+    }
+
+    // Whether every game of the current round has a result
+    pub fn round_is_over(&self) -> bool {
+        self.rounds
+            .last()
+            .into_iter()
+            .flatten()
+            .all(|pairing| pairing.black.is_none() || pairing.result.is_some())
+    }
+
+    // Pairs the next round, or completes the tournament after the last one
+    pub fn next_round(&mut self) -> Option<&mut Vec<TournamentPairing>> {
+        let pairs = self.next_pairs();
+        self.enter_round(pairs)
+    }
+
+    // The pairs of the next round, if the current one is over and rounds remain. The tournament
+    // is only read, so that the search can run on a copy of it.
+    pub fn next_pairs(&self) -> Option<Pairs> {
+        if self.status != TournamentStatus::InProgress
+            || !self.round_is_over()
+            || self.rounds.len() >= self.total_rounds
+        {
+            return None;
+        }
+        Some(match self.configuration.format {
+            TournamentFormat::RoundRobin => self.round_robin_pairs(self.rounds.len()),
+            TournamentFormat::Swiss => self.swiss_pairs(),
+        })
+    }
+
+    // Enters the round paired by [next_pairs], or completes the tournament after the last one
+    fn enter_round(&mut self, pairs: Option<Pairs>) -> Option<&mut Vec<TournamentPairing>> {
+        if self.status != TournamentStatus::InProgress || !self.round_is_over() {
+            return None;
+        }
+        if self.rounds.len() >= self.total_rounds {
+            self.status = TournamentStatus::Completed;
+            return None;
+        }
+
+        let round = pairs?
+            .into_iter()
+            .map(|(white, black)| TournamentPairing {
+                white,
+                black,
+                game_uuid: None,
+                result: None,
+            })
+            .collect();
+        self.rounds.push(round);
+        self.rounds.last_mut()
+    }
+
+    // The circle method: the first player stays put while the others rotate around it
+    fn round_robin_pairs(&self, round: usize) -> Pairs {
+        let mut seats: Vec<Option<&UserUUID>> = self.participants.iter().map(Some).collect();
+        if seats.len() % 2 == 1 {
+            seats.push(None);
+        }
+        let m = seats.len();
+        seats[1..].rotate_right(round % (m - 1));
+
+        (0..m / 2)
+            .filter_map(|i| {
+                let (a, b) = (seats[i], seats[m - 1 - i]);
+                let a_is_white = round % 2 == i % 2;
+                match (a, b) {
+                    (Some(a), Some(b)) if a_is_white => Some((a.clone(), Some(b.clone()))),
+                    (Some(a), Some(b)) => Some((b.clone(), Some(a.clone()))),
+                    (Some(player), None) | (None, Some(player)) => Some((player.clone(), None)),
+                    (None, None) => None,
+                }
+            })
+            .collect()
+    }
+
+    // Pairs players in order of their standing with the highest-placed opponent they have not
+    // met yet, backtracking when that leaves someone without a new opponent. The backtracking
+    // gives up after [PAIRING_ATTEMPTS], as a field without any pairing free of rematches would
+    // otherwise take exponential time to exhaust. With an odd number of players, the
+    // lowest-placed one without a bye sits out.
+    fn swiss_pairs(&self) -> Pairs {
+        let mut unpaired: Vec<UserUUID> = self
+            .standings()
+            .into_iter()
+            .map(|standing| standing.user_uuid)
+            .collect();
+        let mut bye = None;
+
+        if unpaired.len() % 2 == 1 {
+            let had_bye = |user_uuid: &UserUUID| {
+                self.pairings()
+                    .any(|pairing| pairing.black.is_none() && pairing.white == *user_uuid)
+            };
+            let position = unpaired
+                .iter()
+                .rposition(|user_uuid| !had_bye(user_uuid))
+                .unwrap_or(unpaired.len() - 1);
+            bye = Some((unpaired.remove(position), None));
+        }
+
+        // Rematches are only allowed once the search without them has failed. With them, the
+        // first opponent tried always fits, so that search never backtracks.
+        let pairs = self
+            .pair_in_order(&unpaired, false, &mut { PAIRING_ATTEMPTS })
+            .or_else(|| self.pair_in_order(&unpaired, true, &mut { PAIRING_ATTEMPTS }))
+            .unwrap_or_default();
+        let mut pairs: Pairs = pairs
+            .into_iter()
+            .map(|(a, b)| {
+                if self.games_as_white(&a) <= self.games_as_white(&b) {
+                    (a, Some(b))
+                } else {
+                    (b, Some(a))
+                }
+            })
+            .collect();
+        pairs.extend(bye);
+        pairs
+    }
+
+    fn pair_in_order(
+        &self,
+        unpaired: &[UserUUID],
+        allow_rematches: bool,
+        attempts: &mut usize,
+    ) -> Option<Vec<(UserUUID, UserUUID)>> {
+        let Some((player, rest)) = unpaired.split_first() else {
+            return Some(vec![]);
+        };
+        if *attempts == 0 {
+            return None;
+        }
+        *attempts -= 1;
+        rest.iter()
+            .enumerate()
+            .filter(|(_, opponent)| allow_rematches || !self.have_met(player, opponent))
+            .find_map(|(i, opponent)| {
+                let mut others = rest.to_vec();
+                others.remove(i);
+                let mut pairs = self.pair_in_order(&others, allow_rematches, attempts)?;
+                pairs.insert(0, (player.clone(), opponent.clone()));
+                Some(pairs)
+            })
+    }
+
+    fn pairings(&self) -> impl Iterator<Item = &TournamentPairing> {
+        self.rounds.iter().flatten()
+    }
+
+    fn have_met(&self, a: &UserUUID, b: &UserUUID) -> bool {
+        self.pairings().any(|pairing| {
+            let black = pairing.black.as_ref();
+            (pairing.white == *a && black == Some(b)) || (pairing.white == *b && black == Some(a))
+        })
+    }
+
+    fn games_as_white(&self, user_uuid: &UserUUID) -> usize {
+        self.pairings()
+            .filter(|pairing| pairing.black.is_some() && pairing.white == *user_uuid)
+            .count()
+    }
+
+    // Returns whether the game belongs to the tournament and was waiting for its result
+    pub fn record_result(&mut self, game_uuid: &GameUUID, result: GameResult) -> bool {
+        let pairing = self.rounds.iter_mut().flatten().find(|pairing| {
+            pairing.result.is_none() && pairing.game_uuid.as_ref() == Some(game_uuid)
+        });
+        match pairing {
+            Some(pairing) => {
+                pairing.result = Some(result);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The games of the current round that are still being played
+    pub fn pending_games(&self) -> Vec<GameUUID> {
+        self.rounds
+            .last()
+            .into_iter()
+            .flatten()
+            .filter(|pairing| pairing.result.is_none())
+            .filter_map(|pairing| pairing.game_uuid.clone())
+            .collect()
+    }
+
+    // Best first: by points, then Buchholz, then Sonneborn-Berger, then wins
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: HashMap<&UserUUID, Standing> = self
+            .participants
+            .iter()
+            .map(|user_uuid| {
+                let standing = Standing {
+                    user_uuid: user_uuid.clone(),
+                    points: 0.0,
+                    wins: 0,
+                    draws: 0,
+                    losses: 0,
+                    buchholz: 0.0,
+                    sonneborn_berger: 0.0,
+                };
+                (user_uuid, standing)
+            })
+            .collect();
+
+        // Every finished game, from both players' points of view: (player, opponent, points)
+        let mut games: Vec<(&UserUUID, &UserUUID, f64)> = vec![];
+        for pairing in self.pairings() {
+            match (&pairing.black, &pairing.result) {
+                (None, _) => {
+                    if let Some(standing) = standings.get_mut(&pairing.white) {
+                        standing.points += 1.0;
+                        standing.wins += 1;
+                    }
+                }
+                (Some(black), Some(result)) => {
+                    games.push((&pairing.white, black, points(result, PlayerSide::White)));
+                    games.push((black, &pairing.white, points(result, PlayerSide::Black)));
+                }
+                (Some(_), None) => {}
+            }
+        }
+        for &(player, _, points) in &games {
+            if let Some(standing) = standings.get_mut(player) {
+                standing.points += points;
+                if points == 1.0 {
+                    standing.wins += 1;
+                } else if points == 0.5 {
+                    standing.draws += 1;
+                } else {
+                    standing.losses += 1;
+                }
+            }
+        }
+
+        let totals: HashMap<&UserUUID, f64> = standings
+            .iter()
+            .map(|(&user_uuid, standing)| (user_uuid, standing.points))
+            .collect();
+        for &(player, opponent, points) in &games {
+            let opponent_points = totals.get(opponent).copied().unwrap_or(0.0);
+            if let Some(standing) = standings.get_mut(player) {
+                standing.buchholz += opponent_points;
+                standing.sonneborn_berger += points * opponent_points;
+            }
+        }
+
+        // Ties on every count keep the order of registration
+        let mut ordered: Vec<Standing> = self
+            .participants
+            .iter()
+            .filter_map(|user_uuid| standings.remove(user_uuid))
+            .collect();
+        ordered.sort_by(|a, b| {
+            b.points
+                .total_cmp(&a.points)
+                .then(b.buchholz.total_cmp(&a.buchholz))
+                .then(b.sonneborn_berger.total_cmp(&a.sonneborn_berger))
+                .then(b.wins.cmp(&a.wins))
+        });
+        ordered
+    }
+}
+
+pub fn standings_update(tournament: &Tournament) -> Response {
+    Response::TournamentStandings {
+        tournament_uuid: tournament.tournament_uuid.clone(),
+        round: tournament.rounds.len(),
+        status: tournament.status.clone(),
+        standings: tournament.standings(),
+    }
+}
+
+// The result of a finished game, wherever the game is now. A game that is gone without a
+// result was expired before both players joined, which counts as aborted.
+async fn find_result(state: &ServerState, game_uuid: &GameUUID) -> Option<GameResult> {
+    if let Some(game) = find_game(&state.games, game_uuid).await {
+        let (_, meta, _) = game.summary().await.ok()?;
+        return match meta.status {
+            GameState::Completed => meta.result,
+            _ => None,
+        };
+    }
+    let archived = state
+        .archive
+        .read()
+        .await
+        .records()
+        .find(|record| record.game_uuid == *game_uuid)
+        .map(|record| record.game_meta.result.clone());
+    archived.unwrap_or(Some(GameResult {
+        winner: None,
+        termination: Termination::Aborted,
+    }))
+}
+
+// What [advance] decided while [Tournaments] was locked, to be carried out by [start_round] once
+// the lock is released
+#[derive(Debug)]
+pub struct RoundStart {
+    tournament_uuid: TournamentUUID,
+    participants: Vec<UserUUID>,
+    standings: Option<Response>, // After the round that just ended
+    round: usize,                // The round to start, or 0 once the tournament is completed
+    game_configuration: GameConfiguration,
+    games: Vec<(GameUUID, UserUUID, UserUUID)>, // White and black
+}
+
+// Pairs the next round once the current one is over. The pairs are searched on a copy of the
+// tournament, without holding [Tournaments] and off the runtime's workers, and only entered if
+// no other call entered a round in the meantime.
+pub async fn advance(state: &ServerState, tournament_uuid: &TournamentUUID) -> Option<RoundStart> {
+    let snapshot = state.tournaments.read().await.get(tournament_uuid)?.clone();
+    let played = snapshot.rounds.len();
+    let pairs = tokio::task::spawn_blocking(move || snapshot.next_pairs())
+        .await
+        .ok()?;
+
+    let mut tournaments = state.tournaments.write().await;
+    let tournament = tournaments.get_mut(tournament_uuid)?;
+    if tournament.rounds.len() != played {
+        return None;
+    }
+    advance_round(tournament, pairs)
+}
+
+// The round's games are only entered into the pairings once they have started, so the round
+// cannot be found over in the meantime
+fn advance_round(tournament: &mut Tournament, pairs: Option<Pairs>) -> Option<RoundStart> {
+    if tournament.status != TournamentStatus::InProgress || !tournament.round_is_over() {
+        return None;
+    }
+    // Paired on a copy taken before the round was over; the next call pairs it
+    let completing = tournament.rounds.len() >= tournament.total_rounds;
+    if pairs.is_none() && !completing {
+        return None;
+    }
+    let played = tournament.rounds.len();
+    let standings = (played > 0).then(|| standings_update(tournament));
+
+    let configuration = &tournament.configuration;
+    let game_configuration = GameConfiguration {
+        creator_name: configuration.name.clone(),
+        opponent: PlayerType::Human,
+        side_selection: ColorPreference::AlwaysWhite, // The first player seated is white
+        time_control: configuration.time_control.clone(),
+        rated: configuration.rated,
+        private: true, // Followed through the tournament rather than the lobby
+    };
+    let mut round_start = RoundStart {
+        tournament_uuid: tournament.tournament_uuid.clone(),
+        participants: tournament.participants.clone(),
+        standings,
+        round: 0,
+        game_configuration,
+        games: vec![],
+    };
+    let Some(round) = tournament.enter_round(pairs) else {
+        round_start.standings = Some(standings_update(tournament));
+        return Some(round_start);
+    };
+    round_start.games = round
+        .iter()
+        .filter_map(|pairing| {
+            let black = pairing.black.clone()?;
+            let game_uuid = Uuid::new_v4().simple().to_string();
+            Some((game_uuid, pairing.white.clone(), black))
+        })
+        .collect();
+    round_start.round = tournament.rounds.len();
+    Some(round_start)
+}
+
+// Tells the participants the standings, then starts the round's games with both players seated
+// and tells them the pairings. A game that could not start is gone, so it counts as aborted.
+pub async fn start_round(state: &ServerState, round_start: RoundStart) {
+    let RoundStart {
+        tournament_uuid,
+        participants,
+        standings,
+        round,
+        game_configuration,
+        games,
+    } = round_start;
+    if let Some(standings) = &standings {
+        send_to(&state.clients, &participants, standings).await;
+    }
+    if round == 0 {
+        info!("[tournament]: {} completed", tournament_uuid);
+        return;
+    }
+
+    for (game_uuid, white, black) in &games {
+        let seated = launch_seated_game(
+            game_uuid.clone(),
+            (white.clone(), black.clone()),
+            game_configuration.clone(),
+            state,
+        )
+        .await;
+        if let Err(e) = seated {
+            warn!(
+                "[tournament]: could not start a game of {}: {}",
+                tournament_uuid, e
+            );
+        }
+    }
+
+    let pairings = {
+        let mut tournaments = state.tournaments.write().await;
+        let Some(tournament) = tournaments.get_mut(&tournament_uuid) else {
+            return;
+        };
+        let Some(pairings) = tournament.rounds.get_mut(round - 1) else {
+            return;
+        };
+        for pairing in pairings.iter_mut() {
+            pairing.game_uuid = games
+                .iter()
+                .find(|(_, white, _)| *white == pairing.white)
+                .map(|(game_uuid, _, _)| game_uuid.clone());
+        }
+        pairings.clone()
+    };
+    let res = Response::RoundStarted {
+        tournament_uuid: tournament_uuid.clone(),
+        round,
+        pairings,
+    };
+    send_to(&state.clients, &participants, &res).await;
+    info!("[tournament]: {} started round {}", tournament_uuid, round);
+}
+
+// Collects the results of the running tournaments' games, and moves on to the next rounds
+pub async fn run_tournaments(state: ServerState) {
+    let mut ticks = interval(TOURNAMENT_INTERVAL);
+    loop {
+        ticks.tick().await;
+        let running: Vec<(TournamentUUID, Vec<GameUUID>)> = state
+            .tournaments
+            .read()
+            .await
+            .values()
+            .filter(|tournament| tournament.status == TournamentStatus::InProgress)
+            .map(|tournament| {
+                (
+                    tournament.tournament_uuid.clone(),
+                    tournament.pending_games(),
+                )
+            })
+            .collect();
+
+        for (tournament_uuid, pending) in running {
+            let mut results = vec![];
+            for game_uuid in pending {
+                if let Some(result) = find_result(&state, &game_uuid).await {
+                    results.push((game_uuid, result));
+                }
+            }
+
+            // The messages are sent and the next round paired once the lock is released
+            let standings = {
+                let mut tournaments = state.tournaments.write().await;
+                let Some(tournament) = tournaments.get_mut(&tournament_uuid) else {
+                    continue;
+                };
+                let mut finished = vec![];
+                for (game_uuid, result) in results {
+                    if tournament.record_result(&game_uuid, result) {
+                        finished.push(game_uuid);
+                    }
+                }
+                (!finished.is_empty() && !tournament.round_is_over()).then(|| {
+                    (
+                        tournament.participants.clone(),
+                        standings_update(tournament),
+                    )
+                })
+            };
+            if let Some((participants, standings)) = standings {
+                send_to(&state.clients, &participants, &standings).await;
+            }
+            if let Some(round_start) = advance(&state, &tournament_uuid).await {
+                start_round(&state, round_start).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn new_user() -> UserUUID {
+        UserUUID::new(uuid::Uuid::new_v4().to_string()).unwrap()
+    }
+
+    fn new_tournament(format: TournamentFormat, participants: usize) -> Tournament {
+        let creator = new_user();
+        let mut tournament = Tournament::new(
+            "t".to_owned(),
+            creator.clone(),
+            TournamentConfiguration {
+                name: "Test".to_owned(),
+                format,
+                rounds: None,
+                time_control: None,
+                rated: false,
+            },
+        );
+        for _ in 0..participants {
+            tournament.register(new_user()).unwrap();
+        }
+        tournament.start(&creator).unwrap();
+        tournament
+    }
+
+    // Plays the round with the first-listed player winning every game
+    fn play_round(tournament: &mut Tournament) {
+        let round = tournament.next_round().unwrap();
+        for (i, pairing) in round.iter_mut().enumerate() {
+            pairing.game_uuid = Some(i.to_string());
+            if pairing.black.is_some() {
+                pairing.result = Some(GameResult {
+                    winner: Some(PlayerSide::White),
+                    termination: Termination::Apex,
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn round_robin_meets_everyone_once() {
+        let mut tournament = new_tournament(TournamentFormat::RoundRobin, 5);
+        assert_eq!(tournament.total_rounds, 5);
+        for _ in 0..5 {
+            play_round(&mut tournament);
+        }
+        assert!(tournament.next_round().is_none());
+        assert_eq!(tournament.status, TournamentStatus::Completed);
+
+        let mut met = HashSet::new();
+        for pairing in tournament.pairings() {
+            if let Some(black) = &pairing.black {
+                let mut pair = [pairing.white.clone(), black.clone()];
+                pair.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+                assert!(met.insert(pair), "players met twice");
+            }
+        }
+        assert_eq!(met.len(), 10);
+        assert!(tournament
+            .standings()
+            .iter()
+            .all(|s| s.wins + s.losses == 5));
+    }
+
+    #[test]
+    fn swiss_avoids_rematches() {
+        let mut tournament = new_tournament(TournamentFormat::Swiss, 6);
+        assert_eq!(tournament.total_rounds, 3);
+        for _ in 0..3 {
+            play_round(&mut tournament);
+        }
+        assert!(tournament.next_round().is_none());
+
+        let met: HashSet<_> = tournament
+            .pairings()
+            .map(|pairing| {
+                let mut pair = [pairing.white.clone(), pairing.black.clone().unwrap()];
+                pair.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+                pair
+            })
+            .collect();
+        assert_eq!(met.len(), 9);
+        let standings = tournament.standings();
+        assert!(standings[0].points >= standings[1].points);
+        assert!(standings[0].buchholz > 0.0);
+    }
+
+    #[test]
+    fn full_swiss_fields_are_paired_in_bounded_time() {
+        let mut tournament = new_tournament(TournamentFormat::Swiss, MAX_PARTICIPANTS);
+        tournament.status = TournamentStatus::Registering;
+        assert_eq!(
+            tournament.register(new_user()),
+            Err("The tournament is full")
+        );
+        tournament.status = TournamentStatus::InProgress;
+
+        // The late rounds leave no pairing free of rematches, which an exhaustive search
+        // would never finish proving
+        for _ in 0..tournament.total_rounds {
+            play_round(&mut tournament);
+            let round = tournament.rounds.last().unwrap();
+            assert_eq!(round.len(), MAX_PARTICIPANTS / 2);
+        }
+        assert!(tournament.next_round().is_none());
+        assert_eq!(tournament.status, TournamentStatus::Completed);
+    }
+
+    #[test]
+    fn byes_and_results() {
+        let mut tournament = new_tournament(TournamentFormat::Swiss, 3);
+        assert!(tournament.register(new_user()).is_err());
+        let round = tournament.next_round().unwrap();
+        assert_eq!(round.iter().filter(|p| p.black.is_none()).count(), 1);
+        let playing = round.iter_mut().find(|p| p.black.is_some()).unwrap();
+        playing.game_uuid = Some("g".to_owned());
+        assert!(!tournament.round_is_over());
+
+        let draw = GameResult {
+            winner: None,
+            termination: Termination::Agreement,
+        };
+        assert!(tournament.record_result(&"g".to_owned(), draw.clone()));
+        assert!(!tournament.record_result(&"g".to_owned(), draw));
+        assert!(tournament.round_is_over());
+        let points: Vec<f64> = tournament.standings().iter().map(|s| s.points).collect();
+        assert_eq!(points, [1.0, 0.5, 0.5]);
+    }
+
+    #[tokio::test]
+    async fn rounds_start_with_seated_private_games() {
+        let state = ServerState::new(crate::state::server_config::ServerConfig::default());
+        let mut lobby_events = state.lobby.subscribe();
+        let tournament = new_tournament(TournamentFormat::RoundRobin, 2);
+        let tournament_uuid = tournament.tournament_uuid.clone();
+        state
+            .tournaments
+            .write()
+            .await
+            .insert(tournament_uuid.clone(), tournament);
+
+        let round_start = advance(&state, &tournament_uuid).await;
+        start_round(&state, round_start.unwrap()).await;
+
+        let pairing = state.tournaments.read().await[&tournament_uuid].rounds[0][0].clone();
+        let game = find_game(&state.games, pairing.game_uuid.as_ref().unwrap())
+            .await
+            .unwrap();
+        let (_, meta, _) = game.summary().await.unwrap();
+        assert_eq!(meta.status, GameState::InProgress);
+        assert!(game
+            .join(new_user(), Some(crate::state::game::Role::Player))
+            .await
+            .is_err());
+        assert!(lobby_events.try_recv().is_err());
+    }
+}
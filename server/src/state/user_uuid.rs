@@ -1,6 +1,8 @@
 use std::{fmt, str::FromStr};
 
-#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(
+    serde::Deserialize, serde::Serialize, schemars::JsonSchema, Debug, Clone, PartialEq, Eq, Hash,
+)]
 pub struct UserUUID(String);
 
 impl UserUUID {